@@ -0,0 +1,110 @@
+//! Command-line arguments shared by the tools that combine runs at several
+//! temperatures.
+
+use std::fs::File;
+use std::io::BufReader;
+use error;
+use time_series::{Selection, TimeSeries};
+
+/// How the temperature of each `FILE[@TEMPERATURE]` is found, for the usage
+/// of the tools taking them.
+pub const TEMPERATURE_RULE: &str = "\
+Each FILE must be sampled at one temperature, given after '@' or else its tempk,
+which must then be constant; demultiplex replica-exchange runs with remd first.";
+
+/// A time-series file of a run at one temperature, given as `FILE[@T]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunFile {
+    pub filename: String,
+    /// The temperature of the run, if given instead of its tempk.
+    pub temperature: Option<f64>,
+}
+
+impl RunFile {
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg.rfind('@') {
+            Some(i) => Some(RunFile {
+                filename: arg[..i].to_string(),
+                temperature: Some(arg[i + 1..].parse().ok()?),
+            }),
+            None => Some(RunFile { filename: arg.to_string(), temperature: None }),
+        }
+    }
+
+    /// Loads the steps from `from` on, with the temperature of the run: the
+    /// one given, or else that of `TimeSeries::temperature`.
+    pub fn load(&self, from: Option<i32>) -> error::Result<(TimeSeries, f64)> {
+        let selection = Selection { from, ..Selection::default() };
+        let ts = TimeSeries::load(BufReader::new(File::open(&self.filename)?))?.select(&selection);
+        let temperature = match self.temperature {
+            Some(temperature) => temperature,
+            None => ts.temperature()?,
+        };
+        Ok((ts, temperature))
+    }
+}
+
+/// A range of `num_points` evenly spaced temperatures from `min` to `max`,
+/// given as `MIN:MAX:N`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+    pub num_points: usize,
+}
+
+impl Range {
+    pub fn parse(range: &str) -> Option<Self> {
+        let fields: Vec<_> = range.split(':').collect();
+        if fields.len() != 3 {
+            return None;
+        }
+        Some(Range {
+            min: fields[0].parse().ok()?,
+            max: fields[1].parse().ok()?,
+            num_points: fields[2].parse().ok()?,
+        })
+    }
+
+    /// Returns 101 temperatures spanning those of the runs.
+    pub fn spanning(temperatures: &[f64]) -> Self {
+        Range {
+            min: temperatures.iter().cloned().fold(f64::INFINITY, f64::min),
+            max: temperatures.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            num_points: 101,
+        }
+    }
+
+    pub fn temperatures(&self) -> Vec<f64> {
+        let step = match self.num_points {
+            0 | 1 => 0.0,
+            n => (self.max - self.min) / (n - 1) as f64,
+        };
+        (0..self.num_points).map(|i| self.min + i as f64 * step).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_run_file() {
+        assert_eq!(RunFile::parse("run.ts"),
+                   Some(RunFile { filename: "run.ts".to_string(), temperature: None }));
+        assert_eq!(RunFile::parse("a@b/run.ts@310.5"),
+                   Some(RunFile { filename: "a@b/run.ts".to_string(), temperature: Some(310.5) }));
+        assert_eq!(RunFile::parse("run.ts@hot"), None);
+    }
+
+    #[test]
+    fn test_range() {
+        let range = Range::parse("300:310:3").unwrap();
+        assert_eq!(range.temperatures(), vec![300.0, 305.0, 310.0]);
+        assert_eq!(Range { num_points: 1, ..range }.temperatures(), vec![300.0]);
+        assert_eq!(Range::spanning(&[310.0, 290.0, 300.0]),
+                   Range { min: 290.0, max: 310.0, num_points: 101 });
+        assert!(Range::parse("300:310").is_none());
+        assert!(Range::parse("300:310:many").is_none());
+    }
+}
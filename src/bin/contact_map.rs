@@ -1,11 +1,12 @@
-extern crate dcdio;
+extern crate cafetools;
 
 use std::env;
 use std::process;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::collections::HashMap;
-use dcdio::DcdReader;
+use cafetools::npy::{Array, Type};
+use cafetools::trajectory::Trajectory;
 
 const ADDITIONAL_LENGTH: usize = 43;
 
@@ -39,18 +40,32 @@ fn main() {
 
     if args.len() < 4 {
         let program = &args[0];
-        eprintln!("Usage: {} REFERENCE STRUCTURE INDEX", program);
+        eprintln!("Usage: {} REFERENCE STRUCTURE INDEX [OUTPUT.npy]", program);
         process::exit(1);
     }
 
     let refmap = read_reference(&args[1]).unwrap();
-    let dcdreader = DcdReader::new(File::open(&args[2]).unwrap()).unwrap();
+    let mut trajectory = Trajectory::open(&args[2]).unwrap();
     let index = args[3].parse().unwrap();
 
-    if let Some(frame) = dcdreader.frames().nth(index) {
-        let frame = frame.unwrap();
+    if index < trajectory.num_frames() {
+        let frame = trajectory.frame(index).unwrap();
         let num = frame.positions.len() - ADDITIONAL_LENGTH;
 
+        if let Some(output) = args.get(4) {
+            // A float32 matrix with NaN for the pairs without a reference.
+            let values: Vec<_> = (0..num).flat_map(|i| (0..num).map(move |j| (i, j))).map(|(i, j)| {
+                match refmap.get(&(i+1, j+1)) {
+                    Some(reference) => (distance(&frame.positions[i], &frame.positions[j]) - reference) as f64,
+                    None => f64::NAN,
+                }
+            }).collect();
+            let mut writer = BufWriter::new(File::create(output).unwrap());
+            Array::new(Type::Float32, vec![num, num], &values).write(&mut writer).unwrap();
+            writer.flush().unwrap();
+            return;
+        }
+
         for i in 0..num {
             let pos_i = &frame.positions[i];
 
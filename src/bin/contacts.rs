@@ -1,16 +1,11 @@
 extern crate cafetools;
-extern crate dcdio;
 
 use std::env;
 use std::process;
 use std::fs::File;
 use std::io::BufReader;
 use cafetools::native_info::NativeInfo;
-use dcdio::DcdReader;
-
-fn distance(x: &(f32, f32, f32), y: &(f32, f32, f32)) -> f32 {
-    ((x.0 - y.0).powi(2) + (x.1 - y.1).powi(2) + (x.2 - y.2).powi(2)).sqrt()
-}
+use cafetools::trajectory::Trajectory;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -27,10 +22,11 @@ fn main() {
         NativeInfo::load(reader).unwrap()
     };
 
-    let dcdreader = {
-        let file = File::open(&args[2]).unwrap();
-        DcdReader::new(file).unwrap()
-    };
+    let mut trajectory = Trajectory::open(&args[2]).unwrap();
+    if let Err(err) = ninfo.check_num_particles(trajectory.num_particles()) {
+        eprintln!("{}: {}", args[2], err);
+        process::exit(1);
+    }
 
     let contacts = {
         let mut contacts = ninfo.contacts;
@@ -44,14 +40,11 @@ fn main() {
     }
     println!();
 
-    for frame in dcdreader.frames() {
+    for frame in trajectory.frames(.., 1) {
         let frame = frame.unwrap();
         print!("{}", frame.step);
         for contact in &contacts {
-            let &(ref index0, ref index1) = &contact.pair;
-            let dist = distance(&frame.positions[index0.index-1],
-                                &frame.positions[index1.index-1]) as f64;
-            print!(",{}", if dist <= contact.length * 1.2 { 1 } else { 0 });
+            print!(",{}", if contact.is_formed(&frame.positions) { 1 } else { 0 });
         }
         println!();
     }
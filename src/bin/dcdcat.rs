@@ -1,9 +1,11 @@
+extern crate cafetools;
 extern crate dcdio;
 
 use std::env;
 use std::process;
 use std::fs::File;
-use dcdio::{DcdReader, DcdWriter};
+use cafetools::trajectory::Trajectory;
+use dcdio::{DcdHeader, DcdWriter};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -14,42 +16,24 @@ fn main() {
         process::exit(1);
     }
 
-    let num_frames = {
-        let mut num_frames = 0;
-        for fname in &args[1..] {
-            let file = File::open(fname).unwrap();
-            let reader = DcdReader::new(file).unwrap();
-            num_frames += reader.header.num_frames;
-        }
-        num_frames - args[2..].len()
+    // The first frame of a file is left out by the trajectory if it repeats
+    // the last frame of the file before.
+    let mut trajectory = Trajectory::chain(&args[1..]).unwrap();
+
+    let header = DcdHeader {
+        num_frames: trajectory.num_frames(),
+        start_time: trajectory.step(0).unwrap_or(0),
+        step_interval: trajectory.step_interval(),
+        num_fixed_atoms: 0,
+        delta: trajectory.time_step() as f32,
+        title: "Generated by dcdcat".to_string(),
+        num_atoms: trajectory.num_particles(),
     };
+    let outfile = File::create("a.dcd").unwrap();
+    let mut writer = DcdWriter::new(outfile, header).unwrap();
 
-    let mut writer;
-
-    {
-        let file = File::open(&args[1]).unwrap();
-        let reader = DcdReader::new(file).unwrap();
-        let mut header = reader.header.clone();
-        header.title = "Generated by dcdcat".to_string();
-        header.num_frames = num_frames;
-
-        let outfile = File::create("a.dcd").unwrap();
-        writer = DcdWriter::new(outfile, header).unwrap();
-
-        for frame in reader.frames() {
-            let frame = frame.unwrap();
-            writer.write_frame(&frame.positions).unwrap();
-        }
-    }
-
-    for fname in &args[2..] {
-        let file = File::open(fname).unwrap();
-        let reader = DcdReader::new(file).unwrap();
-
-        for frame in reader.frames().skip(1) {
-            let frame = frame.unwrap();
-            writer.write_frame(&frame.positions).unwrap();
-        }
+    for frame in trajectory.frames(.., 1) {
+        let frame = frame.unwrap();
+        writer.write_frame(&frame.positions).unwrap();
     }
 }
-
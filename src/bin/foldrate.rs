@@ -0,0 +1,153 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use cafetools::error::Result;
+use cafetools::rates::{self, DoubleExponential, Passage, SingleExponential};
+use cafetools::time_series::TimeSeries;
+
+struct Options {
+    threshold: f64,
+    column: String,
+    unit: Option<usize>,
+    num_samples: usize,
+    seed: u64,
+    level: f64,
+    paths: Vec<String>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] FILE|DIRECTORY...", program);
+    println!("Estimate the folding rate from the first passage of independent runs to a qscore");
+    println!("threshold, given as time-series FILE(s) or DIRECTORY(ies) of .ts files. Runs that");
+    println!("never fold are censored. Print the passage times, the Kaplan-Meier survival and");
+    println!("single and double exponential fits with bootstrap confidence intervals as CSV.");
+    println!();
+    println!("Options:");
+    println!("    --threshold Q     the qscore at which a run has folded (default: 0.7)");
+    println!("    --column NAME     the column to use instead of qscore");
+    println!("    --unit N          use the rows of unit N instead of the whole system");
+    println!("    --bootstrap N     the number of bootstrap resamples (default: 1000)");
+    println!("    --seed SEED       the seed of the resampling (default: 1)");
+    println!("    --level LEVEL     the confidence level of the intervals (default: 0.95)");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        threshold: 0.7,
+        column: "qscore".to_string(),
+        unit: None,
+        num_samples: 1000,
+        seed: 1,
+        level: 0.95,
+        paths: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--threshold" => options.threshold = iter.next()?.parse().ok()?,
+            "--column" => options.column = iter.next()?.clone(),
+            "--unit" => options.unit = Some(iter.next()?.parse().ok()?),
+            "--bootstrap" => options.num_samples = iter.next()?.parse().ok()?,
+            "--seed" => options.seed = iter.next()?.parse().ok()?,
+            "--level" => options.level = iter.next()?.parse().ok()?,
+            _ if arg.starts_with("--") => return None,
+            _ => options.paths.push(arg.clone()),
+        }
+    }
+
+    if options.paths.is_empty() {
+        return None;
+    }
+    Some(options)
+}
+
+/// Returns the files given, with each directory replaced by its .ts files.
+fn files(paths: &[String]) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !Path::new(path).is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let mut found = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "ts") {
+                found.push(path.to_string_lossy().into_owned());
+            }
+        }
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+fn print_interval(model: &str, parameter: &str, value: f64, samples: &[f64], level: f64) {
+    match rates::confidence_interval(samples, level) {
+        Some((lower, upper)) => println!("{},{},{:e},{:e},{:e}", model, parameter, value, lower, upper),
+        None => println!("{},{},{:e},,", model, parameter, value),
+    }
+}
+
+fn print_rates(options: &Options) -> Result<()> {
+    let mut passages = Vec::new();
+    println!("# first passage");
+    println!("file,time,censored");
+    for filename in files(&options.paths)? {
+        let ts = TimeSeries::load(BufReader::new(File::open(&filename)?))?;
+        let (steps, values) = ts.series(&options.column, options.unit)?;
+        if let Some(passage) = Passage::first(&steps, &values, options.threshold) {
+            println!("{},{},{}", filename, passage.time, passage.censored);
+            passages.push(passage);
+        }
+    }
+
+    println!("# survival");
+    println!("time,survival");
+    println!("0,1");
+    for (time, probability) in rates::survival(&passages) {
+        println!("{},{:.6}", time, probability);
+    }
+
+    println!("# fits");
+    println!("model,parameter,value,lower,upper");
+    if let Some(fit) = SingleExponential::fit(&passages) {
+        let samples = rates::bootstrap(&passages, options.num_samples, options.seed, SingleExponential::fit);
+        let rates: Vec<_> = samples.iter().map(|fit| fit.rate).collect();
+        print_interval("single", "k", fit.rate, &rates, options.level);
+    }
+    if let Some(fit) = DoubleExponential::fit(&passages) {
+        let samples = rates::bootstrap(&passages, options.num_samples, options.seed, DoubleExponential::fit);
+        let parameter = |f: fn(&DoubleExponential) -> f64| samples.iter().map(f).collect::<Vec<_>>();
+        print_interval("double", "a", fit.amplitude, &parameter(|fit| fit.amplitude), options.level);
+        print_interval("double", "k1", fit.fast_rate, &parameter(|fit| fit.fast_rate), options.level);
+        print_interval("double", "k2", fit.slow_rate, &parameter(|fit| fit.slow_rate), options.level);
+    }
+
+    let folded = passages.iter().filter(|passage| !passage.censored).count();
+    eprintln!("{} of {} runs folded", folded, passages.len());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = print_rates(&options) {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,72 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::{self, File};
+use std::io::{BufReader, Write};
+use std::path::Path;
+use std::collections::HashSet;
+use cafetools::input::Input;
+use cafetools::sweep::Sweep;
+
+fn print_usage(program: &str) {
+    let description = "Write an input file for each combination of the values in SWEEP,\n\
+                       based on INPUT, under OUTDIR/<values>/.";
+
+    println!("Usage: {} INPUT SWEEP OUTDIR", program);
+    println!("{}", description);
+}
+
+fn load_input(filename: &str) -> Input {
+    let file = File::open(filename).unwrap();
+    Input::load(BufReader::new(file)).unwrap()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = &args[0];
+
+    if args.len() != 4 {
+        print_usage(program);
+        process::exit(1);
+    }
+
+    let base = load_input(&args[1]);
+    let sweep = {
+        let spec = load_input(&args[2]);
+        match spec.section("sweep") {
+            Some(section) => Sweep::from_section(section).unwrap(),
+            None => {
+                eprintln!("{}: no <<<< sweep block in {}", program, args[2]);
+                process::exit(1);
+            }
+        }
+    };
+
+    // Combinations of the same name would overwrite each other's files.
+    let combinations = sweep.combinations();
+    let mut names = HashSet::new();
+    for combination in &combinations {
+        let name = combination.names().join("/");
+        if !names.insert(name.clone()) {
+            eprintln!("{}: several combinations are named {}", program, name);
+            process::exit(1);
+        }
+    }
+
+    for combination in combinations {
+        let dir = combination.names().iter()
+                             .fold(Path::new(&args[3]).to_path_buf(), |dir, name| dir.join(name));
+        let mut input = base.clone();
+        if let Err(err) = combination.apply(&mut input, &dir.to_string_lossy()) {
+            eprintln!("{}: {}", program, err);
+            process::exit(1);
+        }
+        fs::create_dir_all(&dir).unwrap();
+
+        let filename = dir.join(format!("{}.inp", input.filenames().unwrap().filename));
+        let mut file = File::create(&filename).unwrap();
+        write!(file, "{}", input).unwrap();
+        println!("{}", filename.display());
+    }
+}
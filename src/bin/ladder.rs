@@ -0,0 +1,152 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::BufReader;
+use cafetools::args::{RunFile, TEMPERATURE_RULE};
+use cafetools::error::{Error, Result};
+use cafetools::input::{Input, Replica};
+use cafetools::ladder::{EnergyDistribution, EnergyModel};
+
+struct Options {
+    acceptance: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    energy: String,
+    from: Option<i32>,
+    n_step_exchange: usize,
+    n_period_prob: Option<usize>,
+    input: Option<String>,
+    files: Vec<RunFile>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] FILE[@TEMPERATURE]...", program);
+    println!("Design a temperature ladder for replica exchange from the energies of short test");
+    println!("runs, given as time-series FILE(s), so that neighbouring replicas exchange at the");
+    println!("target acceptance ratio. Print the replica and replica_temperature sections of a");
+    println!("CafeMol input file, and the expected acceptance ratios to standard error.");
+    println!("{}", TEMPERATURE_RULE);
+    println!();
+    println!("Options:");
+    println!("    --acceptance RATIO   the target acceptance ratio (default: 0.2)");
+    println!("    --min T              the lowest temperature (default: that of the runs)");
+    println!("    --max T              the highest temperature (default: that of the runs)");
+    println!("    --energy COLUMN      the energy of the exchanges (default: etot)");
+    println!("    --from STEP          ignore the steps before STEP");
+    println!("    --exchange N         n_step_exchange (default: 1000)");
+    println!("    --period N           n_period_prob (default: none)");
+    println!("    --input INP          take n_step_exchange and n_period_prob from INP");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        acceptance: 0.2,
+        min: None,
+        max: None,
+        energy: "etot".to_string(),
+        from: None,
+        n_step_exchange: 1000,
+        n_period_prob: None,
+        input: None,
+        files: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--acceptance" => options.acceptance = iter.next()?.parse().ok()?,
+            "--min" => options.min = Some(iter.next()?.parse().ok()?),
+            "--max" => options.max = Some(iter.next()?.parse().ok()?),
+            "--energy" => options.energy = iter.next()?.clone(),
+            "--from" => options.from = Some(iter.next()?.parse().ok()?),
+            "--exchange" => options.n_step_exchange = iter.next()?.parse().ok()?,
+            "--period" => options.n_period_prob = Some(iter.next()?.parse().ok()?),
+            "--input" => options.input = Some(iter.next()?.clone()),
+            _ if arg.starts_with("--") => return None,
+            _ => options.files.push(RunFile::parse(arg)?),
+        }
+    }
+
+    if options.files.is_empty() || !(options.acceptance > 0.0 && options.acceptance < 1.0) {
+        return None;
+    }
+    Some(options)
+}
+
+fn design(options: &Options) -> Result<()> {
+    let mut distributions = Vec::new();
+    for file in &options.files {
+        let (ts, temperature) = file.load(options.from)?;
+        let (_, energies) = ts.series(&options.energy, None)?;
+        let distribution = match EnergyDistribution::new(temperature, &energies) {
+            Some(distribution) => distribution,
+            None => return Err(Error::NoSamples(options.energy.clone())),
+        };
+        eprintln!("{}: {} samples at {:.2} K, Cv {:.4} kcal/(mol K)", file.filename, energies.len(),
+                  temperature, distribution.heat_capacity());
+        distributions.push(distribution);
+    }
+
+    let temperatures: Vec<_> = distributions.iter().map(|d| d.temperature).collect();
+    let min = options.min.unwrap_or_else(|| temperatures.iter().cloned().fold(f64::INFINITY, f64::min));
+    let max = options.max.unwrap_or_else(|| temperatures.iter().cloned().fold(f64::NEG_INFINITY, f64::max));
+    let model = match EnergyModel::new(distributions) {
+        Some(model) => model,
+        None => return Err(Error::NoSamples(options.energy.clone())),
+    };
+    let ladder = match model.ladder(min, max, options.acceptance) {
+        Some(ladder) => ladder,
+        None => return Err(Error::NoFit(format!("a ladder from {} to {} K", min, max))),
+    };
+
+    let (mut n_step_exchange, mut n_period_prob) = (options.n_step_exchange, options.n_period_prob);
+    if let Some(ref filename) = options.input {
+        let input = Input::load(BufReader::new(File::open(filename)?))?;
+        if let Some(replica) = input.replica()? {
+            n_step_exchange = replica.n_step_exchange;
+            n_period_prob = replica.n_period_prob;
+        }
+    }
+
+    eprintln!("replica,temperature,acceptance");
+    for (i, &temperature) in ladder.iter().enumerate() {
+        match ladder.get(i + 1) {
+            Some(&next) => eprintln!("{},{:.2},{:.4}", i + 1, temperature, model.acceptance(temperature, next)),
+            None => eprintln!("{},{:.2},", i + 1, temperature),
+        }
+    }
+
+    let replica = Replica {
+        n_replica_temp: ladder.len(),
+        n_step_exchange,
+        n_period_prob,
+        temperatures: ladder,
+    };
+    for (i, section) in replica.to_sections().iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print!("{}", section);
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = design(&options) {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
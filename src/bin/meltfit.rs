@@ -0,0 +1,125 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use cafetools::args::{Range, RunFile, TEMPERATURE_RULE};
+use cafetools::error::{Error, Result};
+use cafetools::time_series;
+
+struct Options {
+    column: String,
+    unit: Option<usize>,
+    from: Option<i32>,
+    threshold: Option<f64>,
+    sloped: bool,
+    range: Option<Range>,
+    files: Vec<RunFile>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] FILE[@TEMPERATURE]...", program);
+    println!("Fit a two-state model to the melting curve of time-series FILE(s) run at several");
+    println!("temperatures and print the points, the parameters with their standard errors and");
+    println!("the fitted curve as CSV. The enthalpy is in kcal/mol.");
+    println!("{}", TEMPERATURE_RULE);
+    println!();
+    println!("Options:");
+    println!("    --column NAME        the column to fit (default: qscore)");
+    println!("    --unit N             use the rows of unit N instead of the whole system");
+    println!("    --from STEP          ignore the steps before STEP");
+    println!("    --threshold Q        fit the fraction of steps at which the column is at least Q");
+    println!("    --flat               fit baselines that do not depend on the temperature");
+    println!("    --range MIN:MAX:N    print the curve at N temperatures in [MIN, MAX]");
+    println!("                         (default: the runs' span)");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        column: "qscore".to_string(),
+        unit: None,
+        from: None,
+        threshold: None,
+        sloped: true,
+        range: None,
+        files: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--column" => options.column = iter.next()?.clone(),
+            "--unit" => options.unit = Some(iter.next()?.parse().ok()?),
+            "--from" => options.from = Some(iter.next()?.parse().ok()?),
+            "--threshold" => options.threshold = Some(iter.next()?.parse().ok()?),
+            "--flat" => options.sloped = false,
+            "--range" => options.range = Some(Range::parse(iter.next()?)?),
+            _ if arg.starts_with("--") => return None,
+            _ => options.files.push(RunFile::parse(arg)?),
+        }
+    }
+
+    if options.files.is_empty() {
+        return None;
+    }
+    Some(options)
+}
+
+fn print_fit(options: &Options) -> Result<()> {
+    let runs = options.files.iter()
+                            .map(|file| file.load(options.from))
+                            .collect::<Result<Vec<_>>>()?;
+    let (points, fit) = time_series::fit_melting_curve(&runs, &options.column, options.unit,
+                                                       options.threshold, options.sloped)?;
+    println!("# points");
+    println!("file,temperature,{},error", options.column);
+    for (file, point) in options.files.iter().zip(&points) {
+        println!("{},{:.2},{:.6},{:.6}", file.filename, point.temperature, point.value,
+                 point.error.unwrap_or(0.0));
+    }
+
+    let fit = match fit {
+        Some(fit) => fit,
+        None => return Err(Error::NoFit(format!("a two-state model to {} points", points.len()))),
+    };
+    let (model, errors) = (fit.model, fit.errors);
+    println!("# parameters");
+    println!("parameter,value,error");
+    println!("dH,{:.6},{:.6}", model.enthalpy, errors.enthalpy);
+    println!("Tm,{:.6},{:.6}", model.melting_temperature, errors.melting_temperature);
+    println!("folded_intercept,{:.6},{:.6}", model.folded.intercept, errors.folded.intercept);
+    println!("folded_slope,{:e},{:e}", model.folded.slope, errors.folded.slope);
+    println!("unfolded_intercept,{:.6},{:.6}", model.unfolded.intercept, errors.unfolded.intercept);
+    println!("unfolded_slope,{:e},{:e}", model.unfolded.slope, errors.unfolded.slope);
+
+    let range = options.range.unwrap_or_else(|| {
+        Range::spanning(&points.iter().map(|point| point.temperature).collect::<Vec<_>>())
+    });
+    println!("# curve");
+    println!("temperature,{},folded", options.column);
+    for temperature in range.temperatures() {
+        println!("{:.2},{:.6},{:.6}", temperature, model.value(temperature),
+                 model.folded_fraction(temperature));
+    }
+
+    eprintln!("Tm: {:.2} +- {:.2} K, dH: {:.2} +- {:.2} kcal/mol", model.melting_temperature,
+              errors.melting_temperature, model.enthalpy, errors.enthalpy);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = print_fit(&options) {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,196 @@
+extern crate cafetools;
+extern crate dcdio;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::io::prelude::*;
+use std::path::Path;
+use cafetools::error::{Error, Result};
+use cafetools::input::Input;
+use cafetools::replica::History;
+use cafetools::time_series::TimeSeries;
+use cafetools::trajectory::Trajectory;
+use dcdio::{DcdHeader, DcdWriter};
+
+struct Options {
+    by_replica: bool,
+    prefix: String,
+    input: Option<String>,
+    history: String,
+    files: Vec<String>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] HISTORY [FILE...]", program);
+    println!("Print the exchange acceptance ratios and round trips of a replica-exchange run");
+    println!("from its HISTORY, and sort the time-series or DCD FILE(s) of replicas 1, 2, ...");
+    println!("into PREFIX_0001.ts, PREFIX_0002.ts, ... (or .dcd) of temperatures 1, 2, ...");
+    println!();
+    println!("Options:");
+    println!("    --by-replica       FILE(s) are per temperature; write one per replica instead");
+    println!("    --prefix PREFIX    the prefix of the written files (default: demux)");
+    println!("    --input INP        the CafeMol input file, to print the temperatures");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut by_replica = false;
+    let mut prefix = "demux".to_string();
+    let mut input = None;
+    let mut files = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--by-replica" => by_replica = true,
+            "--prefix" => prefix = iter.next()?.clone(),
+            "--input" => input = Some(iter.next()?.clone()),
+            _ if arg.starts_with("--") => return None,
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.is_empty() {
+        return None;
+    }
+    Some(Options {
+        by_replica,
+        prefix,
+        input,
+        history: files.remove(0),
+        files,
+    })
+}
+
+fn temperatures(options: &Options) -> Result<Vec<f64>> {
+    match options.input {
+        Some(ref filename) => {
+            let input = Input::load(BufReader::new(File::open(filename)?))?;
+            let replica = input.replica()?.ok_or_else(|| Error::MissingSection("replica".to_string()))?;
+            Ok(replica.temperatures)
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+fn print_report(history: &History, temperatures: &[f64]) {
+    let label = |i: usize| match temperatures.get(i) {
+        Some(temperature) => format!("{}", temperature),
+        None => format!("{}", i + 1),
+    };
+
+    println!("# acceptance");
+    println!("pair,attempted,accepted,ratio");
+    for (i, acceptance) in history.acceptance().iter().enumerate() {
+        println!("{}-{},{},{},{:.4}", label(i), label(i + 1),
+                 acceptance.attempted, acceptance.accepted, acceptance.ratio());
+    }
+
+    println!("# round trips");
+    println!("replica,count,mean_steps");
+    for (i, trips) in history.round_trips().iter().enumerate() {
+        if trips.is_empty() {
+            println!("{},0,", i + 1);
+        } else {
+            let mean = trips.iter().map(|&steps| steps as f64).sum::<f64>() / trips.len() as f64;
+            println!("{},{},{:.1}", i + 1, trips.len(), mean);
+        }
+    }
+}
+
+fn demultiplex(options: &Options) -> Result<()> {
+    let history = History::load(BufReader::new(File::open(&options.history)?))?;
+    print_report(&history, &temperatures(options)?);
+    if options.files.is_empty() {
+        return Ok(());
+    }
+    if options.files.len() != history.num_replicas() {
+        return Err(Error::FileCount { expected: history.num_replicas(), found: options.files.len() });
+    }
+
+    let is_dcd = |filename: &String| {
+        Path::new(filename).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("dcd"))
+    };
+    match options.files.iter().filter(|filename| is_dcd(filename)).count() {
+        0 => demultiplex_ts(&history, options),
+        n if n == options.files.len() => demultiplex_dcd(&history, options),
+        _ => Err(Error::MixedInputs),
+    }
+}
+
+fn demultiplex_ts(history: &History, options: &Options) -> Result<()> {
+    let series = options.files.iter()
+                              .map(|filename| TimeSeries::load(BufReader::new(File::open(filename)?)))
+                              .collect::<Result<Vec<_>>>()?;
+    for (i, ts) in history.demultiplex(&series, options.by_replica).iter().enumerate() {
+        let filename = format!("{}_{:04}.ts", options.prefix, i + 1);
+        let mut writer = BufWriter::new(File::create(&filename)?);
+        write!(writer, "{}", ts)?;
+        eprintln!("{}: {} steps", filename, ts.steps.len());
+    }
+    Ok(())
+}
+
+/// Sorts the frames of DCD files written in step with each other, with the
+/// step of each frame from the header of its file. The frames before the
+/// first exchange are left out.
+fn demultiplex_dcd(history: &History, options: &Options) -> Result<()> {
+    let mut trajectories = options.files.iter()
+                                        .map(Trajectory::open)
+                                        .collect::<Result<Vec<_>>>()?;
+    let num_particles = trajectories[0].num_particles();
+    if let Some(other) = trajectories.iter().find(|trajectory| trajectory.num_particles() != num_particles) {
+        return Err(Error::ParticleCount { expected: num_particles, found: other.num_particles() });
+    }
+    let steps: Vec<Vec<_>> = trajectories.iter().map(|trajectory| {
+        (0..trajectory.num_frames()).filter_map(|frame| trajectory.step(frame))
+                                    .map(|step| step as i32)
+                                    .collect()
+    }).collect();
+    let frames = history.align_frames(&steps, options.by_replica)?;
+
+    let mut writers = Vec::new();
+    for i in 0..trajectories.len() {
+        let header = DcdHeader {
+            num_frames: frames.len(),
+            start_time: frames.first().map_or(0, |frame| frame.step as usize),
+            step_interval: trajectories[0].step_interval(),
+            num_fixed_atoms: 0,
+            delta: trajectories[0].time_step() as f32,
+            title: "Generated by remd".to_string(),
+            num_atoms: num_particles,
+        };
+        let filename = format!("{}_{:04}.dcd", options.prefix, i + 1);
+        writers.push(DcdWriter::new(BufWriter::new(File::create(&filename)?), header)?);
+    }
+
+    for frame in &frames {
+        let positions = trajectories.iter_mut()
+                                    .map(|trajectory| Ok(trajectory.frame(frame.index)?.positions))
+                                    .collect::<Result<Vec<_>>>()?;
+        for (writer, &source) in writers.iter_mut().zip(&frame.sources) {
+            writer.write_frame(&positions[source])?;
+        }
+    }
+    eprintln!("{} frames of {} replicas written", frames.len(), writers.len());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = demultiplex(&options) {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
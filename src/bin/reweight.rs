@@ -0,0 +1,208 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::BufReader;
+use cafetools::args::{Range, RunFile};
+use cafetools::error::{Error, Result};
+use cafetools::geometry;
+use cafetools::native_info::NativeInfo;
+use cafetools::time_series::TimeSeries;
+use cafetools::trajectory::Trajectory;
+use cafetools::wham::{Reweighting, Run};
+
+struct Options {
+    energy: String,
+    columns: Vec<String>,
+    from: Option<i32>,
+    range: Option<Range>,
+    min_fraction: f64,
+    dcd: Option<String>,
+    ninfo: Option<String>,
+    nstep_save: Option<usize>,
+    file: RunFile,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] FILE", program);
+    println!("Reweight the averages of a time-series FILE run at one temperature to nearby");
+    println!("temperatures by the energy and print them as CSV with the heat capacity");
+    println!("(kcal/(mol K)) and the effective sample size. The temperature of the run is the");
+    println!("constant tempk unless given.");
+    println!();
+    println!("Options:");
+    println!("    --energy COLUMN      the energy to reweight by (default: etot)");
+    println!("    --columns NAMES      comma-separated columns to average (default: qscore,radg)");
+    println!("    --from STEP          ignore the steps before STEP");
+    println!("    --temperature T      the temperature of the run");
+    println!("    --range MIN:MAX:N    print N temperatures in [MIN, MAX] (default: T-10:T+10:21)");
+    println!("    --min-fraction F     warn below an effective sample size of F of the samples");
+    println!("                         (default: 0.1)");
+    println!("    --dcd DCD            also average the Rg of the frames of DCD");
+    println!("    --ninfo NINFO        also average the Q of the frames, with --dcd");
+    println!("    --nstep-save N       the steps between frames, overriding the DCD header");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        energy: "etot".to_string(),
+        columns: vec!["qscore".to_string(), "radg".to_string()],
+        from: None,
+        range: None,
+        min_fraction: 0.1,
+        dcd: None,
+        ninfo: None,
+        nstep_save: None,
+        file: RunFile { filename: String::new(), temperature: None },
+    };
+
+    let mut files = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--energy" => options.energy = iter.next()?.clone(),
+            "--columns" => {
+                options.columns = iter.next()?.split(',').map(|name| name.trim().to_string()).collect();
+            }
+            "--from" => options.from = Some(iter.next()?.parse().ok()?),
+            "--temperature" => options.file.temperature = Some(iter.next()?.parse().ok()?),
+            "--range" => options.range = Some(Range::parse(iter.next()?)?),
+            "--min-fraction" => options.min_fraction = iter.next()?.parse().ok()?,
+            "--dcd" => options.dcd = Some(iter.next()?.clone()),
+            "--ninfo" => options.ninfo = Some(iter.next()?.clone()),
+            "--nstep-save" => options.nstep_save = Some(iter.next()?.parse().ok()?),
+            _ if arg.starts_with("--") => return None,
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.len() != 1 || (options.ninfo.is_some() && options.dcd.is_none()) {
+        return None;
+    }
+    options.file.filename = files.pop()?;
+    Some(options)
+}
+
+/// The observables of the frames of a DCD file, with the energy of the time
+/// series at the step of each frame.
+struct Frames {
+    energies: Vec<f64>,
+    rg: Vec<f64>,
+    q: Vec<f64>,
+}
+
+fn frames(options: &Options, dcd: &str, ts: &TimeSeries) -> Result<Frames> {
+    let energy = ts.columns_of(&[&options.energy])?[0];
+    let ninfo = match options.ninfo {
+        Some(ref filename) => Some(NativeInfo::load(BufReader::new(File::open(filename)?))?),
+        None => None,
+    };
+    let mut trajectory = Trajectory::open(dcd)?;
+    if let Some(ref ninfo) = ninfo {
+        ninfo.check_num_particles(trajectory.num_particles())?;
+    }
+
+    let mut frames = Frames { energies: Vec::new(), rg: Vec::new(), q: Vec::new() };
+    let mut joined = trajectory.join(ts, options.nstep_save);
+    for pair in joined.by_ref() {
+        let (frame, time_step) = pair?;
+        let positions = frame.positions;
+        frames.energies.push(time_step.system.values[energy] as f64);
+        frames.rg.push(geometry::radius_of_gyration(&positions));
+        if let Some(ref ninfo) = ninfo {
+            frames.q.push(ninfo.qscore(&positions));
+        }
+    }
+
+    if joined.unmatched > 0 {
+        eprintln!("warning: {} of {} frames have no time-series row", joined.unmatched, joined.num_frames);
+    }
+    if frames.energies.is_empty() {
+        return Err(Error::NoSamples(dcd.to_string()));
+    }
+    Ok(frames)
+}
+
+fn print_reweighting(options: &Options) -> Result<()> {
+    let (ts, temperature) = options.file.load(options.from)?;
+    let (_, energies) = ts.series(&options.energy, None)?;
+    if energies.is_empty() {
+        return Err(Error::NoSamples(options.energy.clone()));
+    }
+    let columns = options.columns.iter()
+                                 .map(|name| ts.series(name, None).map(|(_, values)| values))
+                                 .collect::<Result<Vec<_>>>()?;
+    let frames = match options.dcd {
+        Some(ref dcd) => Some(frames(options, dcd, &ts)?),
+        None => None,
+    };
+    let run = Run::new(temperature, energies);
+    let frame_run = frames.as_ref().map(|frames| Run::new(temperature, frames.energies.clone()));
+    eprintln!("{}: {} samples at {} K", options.file.filename, run.energies.len(), temperature);
+
+    print!("temperature,ess,cv,{}", options.energy);
+    for name in &options.columns {
+        print!(",{}", name);
+    }
+    if let Some(ref frames) = frames {
+        print!(",frame_ess,rg");
+        if !frames.q.is_empty() {
+            print!(",q");
+        }
+    }
+    println!();
+
+    let range = options.range.unwrap_or(Range {
+        min: temperature - 10.0,
+        max: temperature + 10.0,
+        num_points: 21,
+    });
+    let threshold = options.min_fraction * run.energies.len() as f64;
+    let mut unreliable = Vec::new();
+    for t in range.temperatures() {
+        let reweighting = Reweighting::new(&run, t);
+        let ess = reweighting.effective_sample_size();
+        if ess < threshold {
+            unreliable.push(t);
+        }
+        print!("{:.2},{:.1},{:.6},{:.6}", t, ess, reweighting.heat_capacity(&run.energies),
+               reweighting.average(&run.energies));
+        for values in &columns {
+            print!(",{:.6}", reweighting.average(values));
+        }
+        if let (Some(frames), Some(frame_run)) = (frames.as_ref(), frame_run.as_ref()) {
+            let reweighting = Reweighting::new(frame_run, t);
+            print!(",{:.1},{:.6}", reweighting.effective_sample_size(), reweighting.average(&frames.rg));
+            if !frames.q.is_empty() {
+                print!(",{:.6}", reweighting.average(&frames.q));
+            }
+        }
+        println!();
+    }
+
+    if !unreliable.is_empty() {
+        let temperatures: Vec<_> = unreliable.iter().map(|t| format!("{:.2}", t)).collect();
+        eprintln!("warning: the effective sample size is below {:.0} at {} K; reweighting there is \
+                   unreliable", threshold, temperatures.join(", "));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = print_reweighting(&options) {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
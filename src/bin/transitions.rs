@@ -0,0 +1,147 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::BufReader;
+use cafetools::error::Result;
+use cafetools::kinetics::{Cutoffs, Kinetics, State};
+use cafetools::native_info::NativeInfo;
+use cafetools::time_series::TimeSeries;
+use cafetools::trajectory::Trajectory;
+
+struct Options {
+    cutoffs: Cutoffs,
+    column: String,
+    unit: Option<usize>,
+    ninfo: Option<String>,
+    file: String,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] FILE", program);
+    println!("Detect the folding and unfolding transitions in the qscore of a time-series FILE,");
+    println!("or with --ninfo in the Q of each frame of a DCD FILE, and print the transitions,");
+    println!("the dwells in each state and the mean first passage times in steps as CSV.");
+    println!();
+    println!("Options:");
+    println!("    --cutoffs LOW:HIGH   the cores of the unfolded and folded states (default: 0.3:0.7)");
+    println!("    --column NAME        the column to use instead of qscore");
+    println!("    --unit N             use the rows of unit N instead of the whole system, or with");
+    println!("                         --ninfo the contacts within unit N");
+    println!("    --ninfo NINFO        compute Q from the native contacts of NINFO");
+}
+
+fn parse_cutoffs(cutoffs: &str) -> Option<Cutoffs> {
+    let mut fields = cutoffs.split(':');
+    let unfolded = fields.next()?.parse().ok()?;
+    let folded = fields.next()?.parse().ok()?;
+    if fields.next().is_some() || unfolded >= folded {
+        return None;
+    }
+    Some(Cutoffs::new(unfolded, folded))
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut cutoffs = Cutoffs::new(0.3, 0.7);
+    let mut column = "qscore".to_string();
+    let mut unit = None;
+    let mut ninfo = None;
+    let mut files = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--cutoffs" => cutoffs = parse_cutoffs(iter.next()?)?,
+            "--column" => column = iter.next()?.clone(),
+            "--unit" => unit = Some(iter.next()?.parse().ok()?),
+            "--ninfo" => ninfo = Some(iter.next()?.clone()),
+            _ if arg.starts_with("--") => return None,
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.len() != 1 {
+        return None;
+    }
+    Some(Options { cutoffs, column, unit, ninfo, file: files.remove(0) })
+}
+
+/// Returns the Q of each frame of a DCD file with its step, of the whole
+/// system or of `unit`.
+fn qscores(dcd: &str, ninfo: &str, unit: Option<usize>) -> Result<(Vec<i32>, Vec<f64>)> {
+    let ninfo = NativeInfo::load(BufReader::new(File::open(ninfo)?))?;
+    let mut trajectory = Trajectory::open(dcd)?;
+    ninfo.check_num_particles(trajectory.num_particles())?;
+
+    let mut steps = Vec::new();
+    let mut values = Vec::new();
+    for frame in trajectory.frames(.., 1) {
+        let frame = frame?;
+        steps.push(frame.step as i32);
+        values.push(match unit {
+            Some(unit) => ninfo.unit_qscore(&frame.positions, unit),
+            None => ninfo.qscore(&frame.positions),
+        });
+    }
+    Ok((steps, values))
+}
+
+fn mean(values: &[i32]) -> String {
+    if values.is_empty() {
+        String::new()
+    } else {
+        format!("{:.1}", values.iter().map(|&value| value as f64).sum::<f64>() / values.len() as f64)
+    }
+}
+
+fn print_kinetics(options: &Options) -> Result<()> {
+    let (steps, values) = match options.ninfo {
+        Some(ref ninfo) => qscores(&options.file, ninfo, options.unit)?,
+        None => {
+            let ts = TimeSeries::load(BufReader::new(File::open(&options.file)?))?;
+            ts.series(&options.column, options.unit)?
+        }
+    };
+    let kinetics = Kinetics::new(&steps, &values, options.cutoffs);
+
+    println!("# transitions");
+    println!("to,exit,entry,path_duration");
+    for transition in &kinetics.transitions {
+        println!("{},{},{},{}", transition.to, transition.exit, transition.entry,
+                 transition.path_duration());
+    }
+
+    println!("# dwells");
+    println!("state,start,end,duration,censored");
+    for dwell in &kinetics.dwells {
+        println!("{},{},{},{},{}", dwell.state, dwell.start, dwell.end, dwell.duration(), dwell.censored);
+    }
+
+    println!("# summary");
+    println!("from,to,transitions,mean_first_passage_time,mean_path_duration");
+    for &from in &[State::Unfolded, State::Folded] {
+        let paths = kinetics.path_durations(from.other());
+        let mfpt = kinetics.mean_first_passage_time(from).map_or(String::new(), |t| format!("{:.1}", t));
+        println!("{},{},{},{},{}", from, from.other(), paths.len(), mfpt, mean(&paths));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = print_kinetics(&options) {
+        eprintln!("{}: {}: {}", program, options.file, err);
+        process::exit(1);
+    }
+}
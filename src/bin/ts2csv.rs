@@ -3,61 +3,299 @@ extern crate cafetools;
 use std::env;
 use std::process;
 use std::fs::File;
-use std::io::{BufReader, LineWriter};
+use std::io::{self, BufReader, BufWriter, LineWriter};
 use std::io::prelude::*;
-use cafetools::skip_lines;
 use cafetools::error::*;
+use cafetools::npy::{self, Table, Type};
 use cafetools::time_series::*;
 
-fn write_header<W: Write+?Sized>(writer: &mut W) -> std::io::Result<()> {
-    writeln!(writer, "step,tempk,radg,etot,velet,qscore,rmsd")
+/// Which rows are written, and how.
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    /// One row per step of the whole system.
+    System,
+    /// One row per step and unit: `step,unit,column...`.
+    Long,
+    /// One row per step with a column per unit: `step,qscore_1,qscore_2,...`.
+    Wide,
 }
 
-fn write_snapshot<W: Write+?Sized>(writer: &mut W, snapshot: &SnapShot) -> std::io::Result<()> {
-    writeln!(writer, "{},{:.2},{:.2},{:.2},{:.2},{:.3},{:.2}",
-             snapshot.step,
-             snapshot.tempk,
-             snapshot.radg,
-             snapshot.etot,
-             snapshot.velet,
-             snapshot.qscore,
-             snapshot.rmsd)
+struct Options {
+    mode: Mode,
+    columns: Option<Vec<String>>,
+    skip_invalid: bool,
+    input: String,
+    output: Option<String>,
 }
 
-fn convert_all<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> Result<()> {
-    write_header(writer)?;
+fn write_value<W: Write+?Sized>(writer: &mut W, column: &Column, value: f32) -> io::Result<()> {
+    write!(writer, ",{:.*}", column.precision, value)
+}
+
+fn write_header<W: Write+?Sized>(writer: &mut W, mode: Mode, columns: &[&Column],
+                                 num_units: usize) -> io::Result<()> {
+    write!(writer, "step")?;
+    match mode {
+        Mode::System => {
+            for column in columns {
+                write!(writer, ",{}", column.name)?;
+            }
+        }
+        Mode::Long => {
+            write!(writer, ",unit")?;
+            for column in columns {
+                write!(writer, ",{}", column.name)?;
+            }
+        }
+        Mode::Wide => {
+            for column in columns {
+                for unit in 1..num_units + 1 {
+                    write!(writer, ",{}_{}", column.name, unit)?;
+                }
+            }
+        }
+    }
+    writeln!(writer)
+}
 
-    skip_lines(reader, 9)?; // Skip headers
+fn write_snapshot<W: Write+?Sized>(writer: &mut W, snapshot: &SnapShot,
+                                   indices: &[usize]) -> io::Result<()> {
+    for &i in indices {
+        write_value(writer, &snapshot.columns[i], snapshot.values[i])?;
+    }
+    Ok(())
+}
+
+fn write_time_step<W: Write+?Sized>(writer: &mut W, mode: Mode, time_step: &TimeStep,
+                                    indices: &[usize], num_units: usize) -> io::Result<()> {
+    match mode {
+        Mode::System => {
+            write!(writer, "{}", time_step.step)?;
+            write_snapshot(writer, &time_step.system, indices)?;
+            writeln!(writer)?;
+        }
+        Mode::Long => {
+            for snapshot in &time_step.units {
+                let unit = snapshot.unit.trim_start_matches('#');
+                write!(writer, "{},{}", time_step.step, unit)?;
+                write_snapshot(writer, snapshot, indices)?;
+                writeln!(writer)?;
+            }
+        }
+        Mode::Wide => {
+            write!(writer, "{}", time_step.step)?;
+            for &i in indices {
+                for unit in 1..num_units + 1 {
+                    match time_step.unit(unit) {
+                        Some(snapshot) => write_value(writer, &snapshot.columns[i], snapshot.values[i])?,
+                        None => write!(writer, ",")?,
+                    }
+                }
+            }
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
 
-    for line in reader.lines() {
-        let snapshot = line?.parse::<SnapShot>()?;
-        if !snapshot.unit.is_empty() {
-            continue;
+fn load<R: BufRead>(reader: &mut R, options: &Options) -> Result<(TimeSeries, Vec<usize>)> {
+    let ts = TimeSeries::load_with(reader, |err| {
+        if options.skip_invalid {
+            eprintln!("warning: skipped {}", err);
+            Ok(())
+        } else {
+            Err(err)
         }
-        write_snapshot(writer, &snapshot)?;
+    })?;
+
+    let indices = match options.columns {
+        Some(ref names) => ts.columns_of(names)?,
+        None => (0..ts.columns.len()).collect(),
+    };
+    Ok((ts, indices))
+}
+
+fn convert_all<R: BufRead, W: Write+?Sized>(reader: &mut R, writer: &mut W,
+                                            options: &Options) -> Result<()> {
+    let (ts, indices) = load(reader, options)?;
+    let columns: Vec<_> = indices.iter().map(|&i| &ts.columns[i]).collect();
+    let num_units = ts.num_units();
+
+    write_header(writer, options.mode, &columns, num_units)?;
+    for time_step in &ts.steps {
+        write_time_step(writer, options.mode, time_step, &indices, num_units)?;
     }
 
     Ok(())
 }
 
+/// Returns a table of the `mode` rows of `snapshots`, which are those of
+/// the whole system or of a unit for `Mode::System`.
+fn table<'a, I>(ts: &TimeSeries, mode: Mode, indices: &[usize], snapshots: I) -> Table
+    where I: Iterator<Item = (i32, &'a SnapShot)>
+{
+    let mut fields = vec![("step".to_string(), Type::Int32)];
+    if mode == Mode::Long {
+        fields.push(("unit".to_string(), Type::Int32));
+    }
+    for &i in indices {
+        fields.push((ts.columns[i].name.clone(), Type::Float32));
+    }
+    let mut table = Table::new(fields);
+    for (step, snapshot) in snapshots {
+        let mut row = vec![step as f64];
+        if mode == Mode::Long {
+            row.push(snapshot.unit_index().unwrap_or(0) as f64);
+        }
+        row.extend(indices.iter().map(|&i| snapshot.values[i] as f64));
+        table.push(&row);
+    }
+    table
+}
+
+/// Writes the time series as a structured array to a `.npy` file, or with
+/// an array of the whole system and one per unit to a `.npz` file.
+fn convert_to_numpy<R: BufRead>(reader: &mut R, output: &str, options: &Options) -> Result<()> {
+    let (ts, indices) = load(reader, options)?;
+    let num_units = ts.num_units();
+    let mut writer = BufWriter::new(File::create(output)?);
+
+    if output.ends_with(".npz") {
+        let system = table(&ts, Mode::System, &indices,
+                           ts.steps.iter().map(|time_step| (time_step.step, &time_step.system)));
+        let mut arrays = vec![("system".to_string(), system.into_array())];
+        for unit in 1..num_units + 1 {
+            let snapshots = ts.steps.iter().filter_map(|time_step| {
+                time_step.unit(unit).map(|snapshot| (time_step.step, snapshot))
+            });
+            arrays.push((format!("unit_{}", unit), table(&ts, Mode::System, &indices, snapshots).into_array()));
+        }
+        let arrays: Vec<_> = arrays.iter().map(|(name, array)| (name.as_str(), array)).collect();
+        npy::write_npz(&mut writer, &arrays)?;
+        return Ok(writer.flush()?);
+    }
+
+    let array = match options.mode {
+        Mode::System => {
+            table(&ts, Mode::System, &indices,
+                  ts.steps.iter().map(|time_step| (time_step.step, &time_step.system))).into_array()
+        }
+        Mode::Long => {
+            let snapshots = ts.steps.iter().flat_map(|time_step| {
+                time_step.units.iter().map(move |snapshot| (time_step.step, snapshot))
+            });
+            table(&ts, Mode::Long, &indices, snapshots).into_array()
+        }
+        Mode::Wide => {
+            let mut fields = vec![("step".to_string(), Type::Int32)];
+            for &i in &indices {
+                for unit in 1..num_units + 1 {
+                    fields.push((format!("{}_{}", ts.columns[i].name, unit), Type::Float32));
+                }
+            }
+            let mut table = Table::new(fields);
+            for time_step in &ts.steps {
+                let mut row = vec![time_step.step as f64];
+                for &i in &indices {
+                    for unit in 1..num_units + 1 {
+                        row.push(time_step.unit(unit).map_or(f64::NAN, |snapshot| snapshot.values[i] as f64));
+                    }
+                }
+                table.push(&row);
+            }
+            table.into_array()
+        }
+    };
+    array.write(&mut writer)?;
+    Ok(writer.flush()?)
+}
+
+/// Returns whether `output` is written in a NumPy format.
+fn is_numpy(output: &str) -> bool {
+    output.ends_with(".npy") || output.ends_with(".npz")
+}
+
 fn print_usage(program: &str) {
-    println!("Usage: {} INPUT OUTPUT", program);
+    println!("Usage: {} [OPTIONS] INPUT [OUTPUT]", program);
+    println!("Convert a time-series file to CSV, written to OUTPUT or standard output. An OUTPUT");
+    println!("ending in .npy is written as a NumPy array with a field per column, keeping the");
+    println!("float32 values, and one ending in .npz as an archive of the arrays 'system' and");
+    println!("'unit_N' of each unit.");
+    println!();
+    println!("Options:");
+    println!("    --long            one row per step and unit: step,unit,column...");
+    println!("    --wide            one row per step with columns per unit: step,qscore_1,qscore_2,...");
+    println!("    --columns NAMES   comma-separated columns to write (default: all)");
+    println!("    --skip-invalid    skip malformed rows with a warning instead of stopping");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut mode = Mode::System;
+    let mut columns = None;
+    let mut skip_invalid = false;
+    let mut files = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--long" => mode = Mode::Long,
+            "--wide" => mode = Mode::Wide,
+            "--columns" => {
+                let names = iter.next()?;
+                columns = Some(names.split(',').map(|name| name.trim().to_string()).collect());
+            }
+            "--skip-invalid" => skip_invalid = true,
+            _ if arg.starts_with("--") => return None,
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.is_empty() || files.len() > 2 {
+        return None;
+    }
+    let output = files.get(1).cloned();
+    Some(Options {
+        mode,
+        columns,
+        skip_invalid,
+        input: files.swap_remove(0),
+        output,
+    })
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
 
-    if args.len() != 3 {
-        print_usage(&program);
-        process::exit(1);
-    }
-
-    let infile = File::open(&args[1]).unwrap();
-    let outfile = File::create(&args[2]).unwrap();
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
 
+    let infile = File::open(&options.input).unwrap();
     let mut reader = BufReader::new(infile);
-    let mut writer = LineWriter::new(outfile);
 
-    convert_all(&mut reader, &mut writer).unwrap();
+    if let Some(ref output) = options.output {
+        if is_numpy(output) {
+            if let Err(err) = convert_to_numpy(&mut reader, output, &options) {
+                eprintln!("{}: {}: {}", program, options.input, err);
+                process::exit(1);
+            }
+            return;
+        }
+    }
+
+    let stdout = io::stdout();
+    let mut writer: Box<dyn Write> = match options.output {
+        Some(ref output) => Box::new(LineWriter::new(File::create(output).unwrap())),
+        None => Box::new(stdout.lock()),
+    };
+
+    if let Err(err) = convert_all(&mut reader, &mut *writer, &options) {
+        eprintln!("{}: {}: {}", program, options.input, err);
+        process::exit(1);
+    }
 }
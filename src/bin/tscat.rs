@@ -3,56 +3,83 @@ extern crate cafetools;
 use std::env;
 use std::process;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use cafetools::skip_lines;
+use std::io::BufReader;
+use cafetools::error::{Error, Result};
+use cafetools::time_series::TimeSeries;
 
 fn print_usage(program: &str) {
-    let description = "Concatenate time-series FILE(s) to standard output.";
+    let description = "Concatenate time-series FILE(s) of a restarted run to standard output.\n\
+                       Steps repeated or overlapped by a continuation are taken from the continuation.";
 
-    println!("Usage: {} FILE...", program);
+    println!("Usage: {} [--renumber] [--force] FILE...", program);
     println!("{}", description);
+    println!();
+    println!("Options:");
+    println!("    --renumber  shift the steps of each continuation to follow the previous file");
+    println!("    --force     concatenate files whose header lines or units differ");
 }
 
-fn skip_lines_until<R: BufRead, F>(reader: &mut R, f: F) -> std::io::Result<String>
-    where F: Fn(&str) -> bool {
-    loop {
-        let mut buf = String::new();
-        reader.read_line(&mut buf)?;
-        if f(&buf) {
-            return Ok(buf);
+/// Removes `flag` from `args` and returns whether it was given.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(i) => {
+            args.remove(i);
+            true
         }
+        None => false,
     }
 }
 
+fn load(filename: &str) -> Result<TimeSeries> {
+    let file = File::open(filename)?;
+    TimeSeries::load(BufReader::new(file))
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let program = &args[0];
+    let mut args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let renumber = take_flag(&mut args, "--renumber");
+    let force = take_flag(&mut args, "--force");
 
     if args.len() < 2 {
-        print_usage(program);
+        print_usage(&program);
         process::exit(1);
     }
 
-    {
-        let file = File::open(&args[1]).unwrap();
-        let reader = BufReader::new(file);
-
-        for line in reader.lines() {
-            println!("{}", line.unwrap());
-        }
-    }
+    let mut ts = load(&args[1]).unwrap_or_else(|err| {
+        eprintln!("{}: {}: {}", program, args[1], err);
+        process::exit(1);
+    });
 
     for filename in args[2..].iter() {
-        let file = File::open(filename).unwrap();
-        let mut reader = BufReader::new(file);
-
-        skip_lines(&mut reader, 10).unwrap();
-        print!("{}",
-               skip_lines_until(&mut reader,
-                                |s| s.len() >= 5 && s[..5].trim().is_empty()).unwrap());
-
-        for line in reader.lines() {
-            println!("{}", line.unwrap());
+        let result = load(filename).and_then(|other| {
+            if !force {
+                ts.check_header(&other)?;
+            }
+            ts.append(other, renumber)
+        });
+        match result {
+            // A restarted run always repeats the step it restarts from.
+            Ok(dropped) if dropped > 1 => {
+                eprintln!("{}: {}: dropped {} overlapping steps", program, filename, dropped);
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{}: {}: {}", program, filename, err);
+                match err {
+                    Error::NonMonotonicSteps { .. } => {
+                        eprintln!("{}: use --renumber if the steps restart from zero", program);
+                    }
+                    Error::HeaderMismatch { .. } | Error::UnitMismatch { .. } => {
+                        eprintln!("{}: use --force to concatenate them anyway", program);
+                    }
+                    _ => {}
+                }
+                process::exit(1);
+            }
         }
     }
+
+    print!("{}", ts);
 }
@@ -0,0 +1,89 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::io::prelude::*;
+use cafetools::error::Result;
+use cafetools::time_series::{Selection, TimeSeries};
+
+struct Options {
+    selection: Selection,
+    input: String,
+    output: Option<String>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] INPUT [OUTPUT]", program);
+    println!("Select steps and units of a time-series file and write them in the same format,");
+    println!("with the original header, to OUTPUT or standard output.");
+    println!();
+    println!("Options:");
+    println!("    --from STEP      the first step to keep");
+    println!("    --to STEP        the last step to keep");
+    println!("    --stride N       keep every N-th step in range");
+    println!("    --units LIST     comma-separated units to keep (default: all)");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut selection = Selection::default();
+    let mut files = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--from" => selection.from = Some(iter.next()?.parse().ok()?),
+            "--to" => selection.to = Some(iter.next()?.parse().ok()?),
+            "--stride" => selection.stride = iter.next()?.parse().ok()?,
+            "--units" => {
+                let units = iter.next()?.split(',').map(|unit| unit.trim().parse().ok());
+                selection.units = Some(units.collect::<Option<_>>()?);
+            }
+            _ if arg.starts_with("--") => return None,
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.is_empty() || files.len() > 2 {
+        return None;
+    }
+    let output = files.get(1).cloned();
+    Some(Options {
+        selection,
+        input: files.swap_remove(0),
+        output,
+    })
+}
+
+fn filter(options: &Options) -> Result<()> {
+    let ts = TimeSeries::load(BufReader::new(File::open(&options.input)?))?;
+    let selected = ts.select(&options.selection);
+
+    let stdout = io::stdout();
+    let mut writer: Box<dyn Write> = match options.output {
+        Some(ref output) => Box::new(BufWriter::new(File::create(output)?)),
+        None => Box::new(stdout.lock()),
+    };
+    write!(writer, "{}", selected)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = filter(&options) {
+        eprintln!("{}: {}: {}", program, options.input, err);
+        process::exit(1);
+    }
+}
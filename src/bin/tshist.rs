@@ -0,0 +1,184 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::BufReader;
+use cafetools::error::{Error, Result};
+use cafetools::histogram::{Axis, Histogram};
+use cafetools::input::Input;
+use cafetools::statistics;
+use cafetools::time_series::TimeSeries;
+
+const DEFAULT_NUM_BINS: usize = 50;
+
+/// A column to histogram: `NAME[:MIN:MAX[:BINS]]`.
+struct AxisSpec {
+    name: String,
+    range: Option<(f64, f64)>,
+    num_bins: usize,
+}
+
+impl AxisSpec {
+    fn parse(spec: &str) -> Option<Self> {
+        let fields: Vec<_> = spec.split(':').collect();
+        let range = match fields.len() {
+            1 => None,
+            3 | 4 => Some((fields[1].parse().ok()?, fields[2].parse().ok()?)),
+            _ => return None,
+        };
+        let num_bins = match fields.get(3) {
+            Some(bins) => bins.parse().ok()?,
+            None => DEFAULT_NUM_BINS,
+        };
+        Some(AxisSpec {
+            name: fields[0].to_string(),
+            range,
+            num_bins,
+        })
+    }
+}
+
+struct Options {
+    axes: Vec<AxisSpec>,
+    unit: Option<usize>,
+    from: Option<i32>,
+    temperature: Option<f64>,
+    input: Option<String>,
+    files: Vec<String>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] -x COLUMN[:MIN:MAX[:BINS]] [-y COLUMN[:MIN:MAX[:BINS]]] FILE...", program);
+    println!("Print the histogram and the free energy -kT ln P (kcal/mol) of one or two");
+    println!("columns of time-series FILE(s) as CSV. Empty bins have no free energy.");
+    println!();
+    println!("Options:");
+    println!("    --unit N           use the rows of unit N instead of the whole system");
+    println!("    --from STEP        ignore the steps before STEP");
+    println!("    --temperature T    the temperature in K (default: tempk of --input, or the mean tempk)");
+    println!("    --input INP        the CafeMol input file of the run");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        axes: Vec::new(),
+        unit: None,
+        from: None,
+        temperature: None,
+        input: None,
+        files: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-x" | "-y" => options.axes.push(AxisSpec::parse(iter.next()?)?),
+            "--unit" => options.unit = Some(iter.next()?.parse().ok()?),
+            "--from" => options.from = Some(iter.next()?.parse().ok()?),
+            "--temperature" => options.temperature = Some(iter.next()?.parse().ok()?),
+            "--input" => options.input = Some(iter.next()?.clone()),
+            _ if arg.starts_with('-') => return None,
+            _ => options.files.push(arg.clone()),
+        }
+    }
+
+    if options.axes.is_empty() || options.axes.len() > 2 || options.files.is_empty() {
+        return None;
+    }
+    Some(options)
+}
+
+/// Returns the values of `names` at each step of every file, and the tempk
+/// of the whole system.
+fn load_points(options: &Options, names: &[&str]) -> Result<(Vec<Vec<f64>>, Vec<f64>)> {
+    let mut points = Vec::new();
+    let mut tempk = Vec::new();
+    for filename in &options.files {
+        let ts = TimeSeries::load(BufReader::new(File::open(filename)?))?;
+        let series = names.iter()
+                          .map(|name| ts.series(name, options.unit))
+                          .collect::<Result<Vec<_>>>()?;
+        let (steps, _) = series[0].clone();
+        for (i, &step) in steps.iter().enumerate() {
+            if options.from.into_iter().any(|from| step < from) {
+                continue;
+            }
+            points.push(series.iter().map(|(_, values)| values[i]).collect());
+        }
+        if let Ok((steps, values)) = ts.series("tempk", None) {
+            tempk.extend(steps.iter().zip(values)
+                              .filter(|&(&step, _)| options.from.into_iter().all(|from| step >= from))
+                              .map(|(_, value)| value));
+        }
+    }
+    Ok((points, tempk))
+}
+
+fn temperature(options: &Options, tempk: &[f64]) -> Result<f64> {
+    if let Some(temperature) = options.temperature {
+        return Ok(temperature);
+    }
+    if let Some(ref filename) = options.input {
+        let input = Input::load(BufReader::new(File::open(filename)?))?;
+        if let Some(tempk) = input.md_information()?.tempk {
+            return Ok(tempk);
+        }
+    }
+    if tempk.is_empty() {
+        return Err(Error::UnknownColumn("tempk".to_string()));
+    }
+    Ok(statistics::mean(tempk))
+}
+
+fn print_histogram(options: &Options) -> Result<()> {
+    let names: Vec<_> = options.axes.iter().map(|axis| axis.name.as_str()).collect();
+    let (points, tempk) = load_points(options, &names)?;
+    let temperature = temperature(options, &tempk)?;
+
+    let mut axes = Vec::new();
+    for (i, spec) in options.axes.iter().enumerate() {
+        let axis = match spec.range {
+            Some((min, max)) => Axis::new(min, max, spec.num_bins),
+            None => {
+                let values: Vec<_> = points.iter().map(|point| point[i]).collect();
+                Axis::spanning(&values, spec.num_bins).unwrap_or_else(|| Axis::new(0.0, 1.0, spec.num_bins))
+            }
+        };
+        axes.push(axis);
+    }
+
+    let mut histogram = Histogram::new(axes);
+    for point in &points {
+        histogram.add(point);
+    }
+    let probabilities = histogram.probabilities();
+    let free_energy = histogram.free_energy(temperature);
+
+    eprintln!("temperature: {} K", temperature);
+    println!("{},count,probability,free_energy", names.join(","));
+    for (i, &count) in histogram.counts.iter().enumerate() {
+        let centers: Vec<_> = histogram.centers(i).iter().map(|x| format!("{:.6}", x)).collect();
+        let free_energy = free_energy[i].map_or(String::new(), |f| format!("{:.6}", f));
+        println!("{},{},{:.6},{}", centers.join(","), count, probabilities[i], free_energy);
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = print_histogram(&options) {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
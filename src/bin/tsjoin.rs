@@ -0,0 +1,214 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::BufReader;
+use cafetools::error::Result;
+use cafetools::geometry::{self, Vector3d};
+use cafetools::input::Input;
+use cafetools::native_info::NativeInfo;
+use cafetools::time_series::TimeSeries;
+use cafetools::trajectory::Trajectory;
+
+struct Options {
+    columns: Option<Vec<String>>,
+    nstep_save: Option<usize>,
+    input: Option<String>,
+    ninfo: Option<String>,
+    rmsd: bool,
+    distances: Vec<(usize, usize)>,
+    ts: String,
+    dcd: String,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] TS DCD", program);
+    println!("Join the rows of a time-series file with the frames of a DCD file of the same run");
+    println!("and print the ts columns and observables of each frame as CSV. Each frame is taken");
+    println!("at its step in the DCD header, or at step ISTART + i * N for frame i with");
+    println!("--nstep-save N.");
+    println!();
+    println!("Options:");
+    println!("    --columns NAMES     comma-separated ts columns to print (default: all)");
+    println!("    --nstep-save N      the steps between frames, overriding the DCD header");
+    println!("    --input INP         the CafeMol input file, to check nstep_save");
+    println!("    --ninfo NINFO       also print Rg and Q of each unit, and Q of the whole system");
+    println!("    --rmsd              print the RMSD from the first frame");
+    println!("    --distance I:J      print the distance between particles I and J (repeatable)");
+}
+
+fn parse_pair(pair: &str) -> Option<(usize, usize)> {
+    let mut fields = pair.split(':');
+    let pair = (fields.next()?.parse().ok()?, fields.next()?.parse().ok()?);
+    if fields.next().is_some() || pair.0 == 0 || pair.1 == 0 {
+        return None;
+    }
+    Some(pair)
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut columns = None;
+    let mut nstep_save = None;
+    let mut input = None;
+    let mut ninfo = None;
+    let mut rmsd = false;
+    let mut distances = Vec::new();
+    let mut files = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--columns" => {
+                columns = Some(iter.next()?.split(',').map(|name| name.trim().to_string()).collect());
+            }
+            "--nstep-save" => nstep_save = Some(iter.next()?.parse().ok()?),
+            "--input" => input = Some(iter.next()?.clone()),
+            "--ninfo" => ninfo = Some(iter.next()?.clone()),
+            "--rmsd" => rmsd = true,
+            "--distance" => distances.push(parse_pair(iter.next()?)?),
+            _ if arg.starts_with("--") => return None,
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.len() != 2 {
+        return None;
+    }
+    Some(Options {
+        columns,
+        nstep_save,
+        input,
+        ninfo,
+        rmsd,
+        distances,
+        dcd: files.pop()?,
+        ts: files.pop()?,
+    })
+}
+
+/// Returns the steps between frames given to override the DCD header,
+/// warning about the sampling intervals that disagree with that of the
+/// frames.
+fn nstep_save(options: &Options, ts: &TimeSeries, dcd_interval: usize) -> Result<Option<usize>> {
+    let nstep_save = options.nstep_save.unwrap_or(dcd_interval);
+    if let Some(ref filename) = options.input {
+        let input = Input::load(BufReader::new(File::open(filename)?))?;
+        let from_input = input.md_information()?.n_step_save;
+        if from_input != nstep_save {
+            eprintln!("warning: the input file saves every {} steps, not every {}", from_input, nstep_save);
+        }
+    }
+    if dcd_interval != nstep_save {
+        eprintln!("warning: the DCD header saves every {} steps, not every {}", dcd_interval, nstep_save);
+    }
+    if let (Some(first), Some(second)) = (ts.steps.first(), ts.steps.get(1)) {
+        let ts_interval = second.step - first.step;
+        if ts_interval != nstep_save as i32 {
+            eprintln!("warning: the time series is written every {} steps, not every {}",
+                      ts_interval, nstep_save);
+        }
+    }
+    Ok(options.nstep_save)
+}
+
+fn subset(positions: &[Vector3d], particles: &[usize]) -> Vec<Vector3d> {
+    particles.iter().filter_map(|&i| positions.get(i - 1).cloned()).collect()
+}
+
+fn join(options: &Options) -> Result<()> {
+    let ts = TimeSeries::load(BufReader::new(File::open(&options.ts)?))?;
+    let indices = match options.columns {
+        Some(ref names) => ts.columns_of(names)?,
+        None => (0..ts.columns.len()).collect(),
+    };
+    let ninfo = match options.ninfo {
+        Some(ref filename) => Some(NativeInfo::load(BufReader::new(File::open(filename)?))?),
+        None => None,
+    };
+    let units = ninfo.as_ref().map(NativeInfo::unit_particles).unwrap_or_default();
+
+    let mut trajectory = Trajectory::open(&options.dcd)?;
+    if let Some(ref ninfo) = ninfo {
+        ninfo.check_num_particles(trajectory.num_particles())?;
+    }
+    let nstep_save = nstep_save(options, &ts, trajectory.step_interval())?;
+
+    print!("step");
+    for &i in &indices {
+        print!(",{}", ts.columns[i].name);
+    }
+    print!(",rg");
+    for unit in units.keys() {
+        print!(",rg_{}", unit);
+    }
+    if ninfo.is_some() {
+        print!(",q");
+        for unit in units.keys() {
+            print!(",q_{}", unit);
+        }
+    }
+    if options.rmsd {
+        print!(",rmsd");
+    }
+    for &(i, j) in &options.distances {
+        print!(",d_{}_{}", i, j);
+    }
+    println!();
+
+    let mut reference = None;
+    let mut joined = trajectory.join(&ts, nstep_save);
+    for pair in joined.by_ref() {
+        let (frame, time_step) = pair?;
+        let positions = frame.positions;
+
+        print!("{}", frame.step);
+        for &i in &indices {
+            print!(",{:.*}", ts.columns[i].precision, time_step.system.values[i]);
+        }
+        print!(",{:.4}", geometry::radius_of_gyration(&positions));
+        for particles in units.values() {
+            print!(",{:.4}", geometry::radius_of_gyration(&subset(&positions, particles)));
+        }
+        if let Some(ref ninfo) = ninfo {
+            print!(",{:.4}", ninfo.qscore(&positions));
+            for &unit in units.keys() {
+                print!(",{:.4}", ninfo.unit_qscore(&positions, unit));
+            }
+        }
+        if options.rmsd {
+            let reference = reference.get_or_insert_with(|| positions.clone());
+            print!(",{:.4}", geometry::rmsd(&positions, reference));
+        }
+        for &(i, j) in &options.distances {
+            match (positions.get(i - 1), positions.get(j - 1)) {
+                (Some(x), Some(y)) => print!(",{:.4}", geometry::distance(x, y)),
+                _ => print!(","),
+            }
+        }
+        println!();
+    }
+
+    if joined.unmatched > 0 {
+        eprintln!("warning: {} of {} frames have no time-series row", joined.unmatched, joined.num_frames);
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = join(&options) {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,120 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::BufReader;
+use cafetools::error::Result;
+use cafetools::statistics::{self, Summary};
+use cafetools::time_series::TimeSeries;
+
+/// The number of cut-offs tried to detect equilibration.
+const NUM_CANDIDATES: usize = 100;
+
+struct Options {
+    unit: Option<usize>,
+    from: Option<i32>,
+    to: Option<i32>,
+    equilibrate: bool,
+    input: String,
+    columns: Vec<String>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] FILE COLUMN...", program);
+    println!("Print statistics of the COLUMN(s) of a time-series FILE as CSV.");
+    println!("The autocorrelation time is given in steps.");
+    println!();
+    println!("Options:");
+    println!("    --unit N       use the rows of unit N instead of the whole system");
+    println!("    --from STEP    ignore the steps before STEP");
+    println!("    --to STEP      ignore the steps after STEP");
+    println!("    --equilibrate  detect the end of equilibration and ignore the steps before it");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        unit: None,
+        from: None,
+        to: None,
+        equilibrate: false,
+        input: String::new(),
+        columns: Vec::new(),
+    };
+    let mut files = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--unit" => options.unit = Some(iter.next()?.parse().ok()?),
+            "--from" => options.from = Some(iter.next()?.parse().ok()?),
+            "--to" => options.to = Some(iter.next()?.parse().ok()?),
+            "--equilibrate" => options.equilibrate = true,
+            _ if arg.starts_with("--") => return None,
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.len() < 2 {
+        return None;
+    }
+    options.input = files.remove(0);
+    options.columns = files;
+    Some(options)
+}
+
+fn print_statistics(options: &Options) -> Result<()> {
+    let ts = TimeSeries::load(BufReader::new(File::open(&options.input)?))?;
+
+    println!("column,start,count,mean,variance,min,max,block_error,autocorrelation_time");
+    for name in &options.columns {
+        let (steps, values) = ts.series(name, options.unit)?;
+        let (steps, mut values): (Vec<_>, Vec<_>) = steps.into_iter().zip(values).filter(|&(step, _)| {
+            options.from.into_iter().all(|from| step >= from) && options.to.into_iter().all(|to| step <= to)
+        }).unzip();
+
+        let mut start = 0;
+        if options.equilibrate {
+            start = statistics::equilibration_index(&values, NUM_CANDIDATES);
+            values.drain(..start);
+        }
+
+        let summary = match Summary::new(&values) {
+            Some(summary) => summary,
+            None => {
+                eprintln!("warning: no steps of '{}' in the window", name);
+                continue;
+            }
+        };
+        let interval = if steps.len() > 1 { (steps[1] - steps[0]) as f64 } else { 1.0 };
+        println!("{},{},{},{:.6},{:.6},{:.6},{:.6},{:.6},{:.1}",
+                 name,
+                 steps[start],
+                 summary.count,
+                 summary.mean,
+                 summary.variance,
+                 summary.min,
+                 summary.max,
+                 summary.block_error,
+                 summary.autocorrelation_time * interval);
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = print_statistics(&options) {
+        eprintln!("{}: {}: {}", program, options.input, err);
+        process::exit(1);
+    }
+}
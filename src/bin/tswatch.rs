@@ -0,0 +1,301 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use std::thread;
+use std::fs::File;
+use std::io::{self, SeekFrom};
+use std::io::prelude::*;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use cafetools::time_series::{Parser, TimeStep};
+use cafetools::trajectory::Trajectory;
+
+/// The columns shown in the status line, when present.
+const WATCHED: [&str; 3] = ["qscore", "radg", "etot"];
+
+/// The range a column must stay in, such as the energy of a run that has not
+/// blown up.
+#[derive(Clone)]
+struct Bound {
+    name: String,
+    min: f64,
+    max: f64,
+}
+
+struct Options {
+    interval: u64,
+    once: bool,
+    dcd: bool,
+    bounds: Vec<Bound>,
+    files: Vec<String>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] FILE...", program);
+    println!("Follow time-series FILE(s) of running simulations and print a status line per run");
+    println!("with the last step, the current and mean qscore, radg and etot, and any NaN or");
+    println!("malformed (overflowed) row, or value out of bounds.");
+    println!();
+    println!("Options:");
+    println!("    --interval SECONDS   the time between updates (default: 10)");
+    println!("    --once               print the status once and exit, with 1 if any run has a problem");
+    println!("                         or cannot be read");
+    println!("    --dcd                also count the frames of the DCD file next to each FILE");
+    println!("    --bound NAME:MIN:MAX report a problem when column NAME leaves [MIN, MAX], where");
+    println!("                         either may be empty (repeatable, e.g. etot:-5000:0)");
+}
+
+fn parse_bound(bound: &str) -> Option<Bound> {
+    let fields: Vec<_> = bound.split(':').collect();
+    if fields.len() != 3 || fields[0].is_empty() {
+        return None;
+    }
+    let limit = |field: &str, default: f64| if field.is_empty() { Some(default) } else { field.parse().ok() };
+    Some(Bound {
+        name: fields[0].to_string(),
+        min: limit(fields[1], f64::NEG_INFINITY)?,
+        max: limit(fields[2], f64::INFINITY)?,
+    })
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        interval: 10,
+        once: false,
+        dcd: false,
+        bounds: Vec::new(),
+        files: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--interval" => options.interval = iter.next()?.parse().ok()?,
+            "--once" => options.once = true,
+            "--dcd" => options.dcd = true,
+            "--bound" => options.bounds.push(parse_bound(iter.next()?)?),
+            _ if arg.starts_with("--") => return None,
+            _ => options.files.push(arg.clone()),
+        }
+    }
+
+    if options.files.is_empty() {
+        return None;
+    }
+    Some(options)
+}
+
+/// The running mean of a column.
+#[derive(Clone, Copy, Default)]
+struct Mean {
+    sum: f64,
+    count: usize,
+}
+
+impl Mean {
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn value(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+}
+
+/// A time-series file being followed.
+struct Run {
+    filename: String,
+    offset: u64,
+    partial: Vec<u8>,
+    parser: Parser,
+    last: Option<TimeStep>,
+    means: Vec<Option<(usize, Mean)>>,
+    bounds: Vec<Bound>,
+    /// The column index of each bound, if present.
+    bounded: Vec<Option<usize>>,
+    problem: Option<String>,
+    updated: Instant,
+    /// The DCD file of the run, whose complete frames are counted.
+    dcd: Option<String>,
+}
+
+impl Run {
+    fn new(filename: &str, bounds: &[Bound]) -> Self {
+        Run {
+            filename: filename.to_string(),
+            offset: 0,
+            partial: Vec::new(),
+            parser: Parser::new(),
+            last: None,
+            means: Vec::new(),
+            bounds: bounds.to_vec(),
+            bounded: Vec::new(),
+            problem: None,
+            updated: Instant::now(),
+            dcd: None,
+        }
+    }
+
+    /// Reads the lines appended since the last update.
+    fn update(&mut self) -> io::Result<()> {
+        let mut file = File::open(&self.filename)?;
+        let length = file.metadata()?.len();
+        if length < self.offset {
+            // The file was truncated by a restart.
+            let dcd = self.dcd.take();
+            *self = Run::new(&self.filename, &self.bounds);
+            self.dcd = dcd;
+        }
+        if length == self.offset {
+            return Ok(());
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+        self.offset += buffer.len() as u64;
+        self.updated = Instant::now();
+        self.partial.extend(buffer);
+
+        // Only complete lines are parsed; the rest waits for the next update.
+        let end = match self.partial.iter().rposition(|&byte| byte == b'\n') {
+            Some(end) => end + 1,
+            None => return Ok(()),
+        };
+        let complete: Vec<u8> = self.partial.drain(..end).collect();
+        for line in String::from_utf8_lossy(&complete).lines() {
+            match self.parser.push(line) {
+                Ok(Some(time_step)) => {
+                    self.check(&time_step);
+                    self.add(time_step);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    if self.problem.is_none() {
+                        self.problem = Some(err.to_string());
+                    }
+                }
+            }
+        }
+        // The last step is only returned once the next one starts, but its
+        // system row is already complete.
+        if let Some(pending) = self.parser.peek().cloned() {
+            self.check(&pending);
+        }
+        Ok(())
+    }
+
+    /// Reports a NaN or a value out of bounds at `time_step`.
+    fn check(&mut self, time_step: &TimeStep) {
+        if self.means.is_empty() {
+            let columns = &time_step.system.columns;
+            let position = |name: &str| columns.iter().position(|column| column.name == name);
+            self.means = WATCHED.iter()
+                                .map(|name| position(name).map(|index| (index, Mean::default())))
+                                .collect();
+            self.bounded = self.bounds.iter().map(|bound| position(&bound.name)).collect();
+            if let Some(i) = self.bounded.iter().position(Option::is_none) {
+                self.problem = Some(format!("no column '{}' to bound", self.bounds[i].name));
+            }
+        }
+        let values = &time_step.system.values;
+        if self.problem.is_none() && values.iter().any(|value| !value.is_finite()) {
+            self.problem = Some(format!("NaN at step {}", time_step.step));
+        }
+        for (bound, index) in self.bounds.iter().zip(&self.bounded) {
+            if let (None, &Some(index)) = (self.problem.as_ref(), index) {
+                let value = values[index] as f64;
+                if value < bound.min || value > bound.max {
+                    let precision = time_step.system.columns[index].precision;
+                    self.problem = Some(format!("{} {:.*} out of bounds at step {}", bound.name, precision,
+                                                value, time_step.step));
+                }
+            }
+        }
+    }
+
+    /// Adds a step, of which all unit rows were read, to the means.
+    fn add(&mut self, time_step: TimeStep) {
+        let values = &time_step.system.values;
+        for &mut (index, ref mut mean) in self.means.iter_mut().flatten() {
+            mean.add(values[index] as f64);
+        }
+        self.last = Some(time_step);
+    }
+
+    fn status(&self, interval: Duration) -> String {
+        let mut status = format!("{}:", self.filename);
+        // The pending step is shown, but only complete steps are in the means.
+        match self.parser.peek().or(self.last.as_ref()) {
+            Some(latest) => {
+                status += &format!(" step {}", latest.step);
+                for (name, mean) in WATCHED.iter().zip(&self.means) {
+                    if let Some((index, ref mean)) = *mean {
+                        let precision = latest.system.columns[index].precision;
+                        status += &format!(" {} {:.*}", name, precision, latest.system.values[index]);
+                        if mean.count > 0 {
+                            status += &format!(" ({:.*})", precision, mean.value());
+                        }
+                    }
+                }
+            }
+            None => status += " no steps yet",
+        }
+        if let Some(ref dcd) = self.dcd {
+            if let Ok(trajectory) = Trajectory::open(dcd) {
+                status += &format!(" frames {}", trajectory.num_frames());
+            }
+        }
+        let idle = self.updated.elapsed();
+        if idle > interval {
+            status += &format!(" idle {}s", idle.as_secs());
+        }
+        match self.problem {
+            Some(ref problem) => status + " PROBLEM: " + problem,
+            None => status + " OK",
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    let interval = Duration::from_secs(options.interval);
+    let mut runs: Vec<_> = options.files.iter().map(|filename| Run::new(filename, &options.bounds)).collect();
+    loop {
+        let mut unreadable = false;
+        for run in &mut runs {
+            if options.dcd && run.dcd.is_none() {
+                let dcd = Path::new(&run.filename).with_extension("dcd");
+                if dcd.exists() {
+                    run.dcd = Some(dcd.to_string_lossy().into_owned());
+                }
+            }
+            match run.update() {
+                Ok(()) => println!("{}", run.status(interval)),
+                Err(err) => {
+                    println!("{}: {}", run.filename, err);
+                    unreadable = true;
+                }
+            }
+        }
+
+        if options.once {
+            let failed = unreadable || runs.iter().any(|run| run.problem.is_some());
+            process::exit(if failed { 1 } else { 0 });
+        }
+        println!();
+        thread::sleep(interval);
+    }
+}
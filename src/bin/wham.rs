@@ -0,0 +1,147 @@
+extern crate cafetools;
+
+use std::env;
+use std::process;
+use cafetools::args::{Range, RunFile, TEMPERATURE_RULE};
+use cafetools::error::{Error, Result};
+use cafetools::wham::{self, DensityOfStates, Run};
+
+const TOLERANCE: f64 = 1e-7;
+const MAX_ITERATIONS: usize = 100_000;
+
+struct Options {
+    energy: String,
+    columns: Vec<String>,
+    from: Option<i32>,
+    num_bins: usize,
+    range: Option<Range>,
+    dos: bool,
+    files: Vec<RunFile>,
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [OPTIONS] FILE[@TEMPERATURE]...", program);
+    println!("Combine time-series FILE(s) run at several temperatures by WHAM and print");
+    println!("the heat capacity (kcal/(mol K)) and reweighted averages as CSV. The melting");
+    println!("temperature at the heat capacity peak is printed to standard error.");
+    println!("{}", TEMPERATURE_RULE);
+    println!();
+    println!("Options:");
+    println!("    --energy COLUMN      the energy to reweight (default: etot)");
+    println!("    --columns NAMES      comma-separated columns to average (default: qscore,radg)");
+    println!("    --from STEP          ignore the steps before STEP");
+    println!("    --bins N             the number of energy bins (default: 200)");
+    println!("    --range MIN:MAX:N    print N temperatures in [MIN, MAX] (default: the runs' span)");
+    println!("    --dos                print the logarithm of the density of states instead");
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        energy: "etot".to_string(),
+        columns: vec!["qscore".to_string(), "radg".to_string()],
+        from: None,
+        num_bins: 200,
+        range: None,
+        dos: false,
+        files: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--energy" => options.energy = iter.next()?.clone(),
+            "--columns" => {
+                options.columns = iter.next()?.split(',').map(|name| name.trim().to_string()).collect();
+            }
+            "--from" => options.from = Some(iter.next()?.parse().ok()?),
+            "--bins" => options.num_bins = iter.next()?.parse().ok()?,
+            "--range" => options.range = Some(Range::parse(iter.next()?)?),
+            "--dos" => options.dos = true,
+            _ if arg.starts_with("--") => return None,
+            _ => options.files.push(RunFile::parse(arg)?),
+        }
+    }
+
+    if options.files.is_empty() || options.num_bins == 0 {
+        return None;
+    }
+    Some(options)
+}
+
+fn print_wham(options: &Options) -> Result<()> {
+    let mut runs = Vec::new();
+    let mut columns = vec![Vec::new(); options.columns.len()];
+    for file in &options.files {
+        let (ts, temperature) = file.load(options.from)?;
+        let (_, energies) = ts.series(&options.energy, None)?;
+        for (values, name) in columns.iter_mut().zip(&options.columns) {
+            values.extend(ts.series(name, None)?.1);
+        }
+        eprintln!("{}: {} samples at {} K", file.filename, energies.len(), temperature);
+        runs.push(Run::new(temperature, energies));
+    }
+
+    let dos = match DensityOfStates::estimate(&runs, options.num_bins, TOLERANCE, MAX_ITERATIONS) {
+        Some(dos) => dos,
+        None => return Err(Error::NoSamples(options.energy.clone())),
+    };
+    if dos.iterations == MAX_ITERATIONS {
+        eprintln!("warning: WHAM did not converge in {} iterations", MAX_ITERATIONS);
+    }
+
+    if options.dos {
+        println!("{},log_g", options.energy);
+        for (energy, log_g) in dos.energies.iter().zip(&dos.log_g) {
+            if let Some(log_g) = *log_g {
+                println!("{:.6},{:.6}", energy, log_g);
+            }
+        }
+        return Ok(());
+    }
+
+    let range = options.range.unwrap_or_else(|| {
+        Range::spanning(&runs.iter().map(|run| run.temperature).collect::<Vec<_>>())
+    });
+    let energies: Vec<_> = runs.iter().flat_map(|run| run.energies.iter().cloned()).collect();
+    let averages: Vec<_> = columns.iter()
+                                  .map(|values| wham::bin_averages(&dos.axis, &energies, values))
+                                  .collect();
+
+    print!("temperature,cv,{}", options.energy);
+    for name in &options.columns {
+        print!(",{}", name);
+    }
+    println!();
+    for temperature in range.temperatures() {
+        print!("{:.2},{:.6},{:.6}", temperature, dos.heat_capacity(temperature),
+               dos.mean_energy(temperature));
+        for values in &averages {
+            print!(",{:.6}", dos.average(temperature, values));
+        }
+        println!();
+    }
+
+    match dos.melting_temperature(range.min, range.max, range.num_points) {
+        Some(tm) => eprintln!("Tm: {:.2} K", tm),
+        None => eprintln!("Tm: no heat capacity peak between {} and {} K", range.min, range.max),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            print_usage(&program);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = print_wham(&options) {
+        eprintln!("{}: {}", program, err);
+        process::exit(1);
+    }
+}
@@ -1,59 +1,304 @@
+use error::{Error, Result};
+use std::fmt;
 use std::io;
 
+/// A labelled `<<<< label ... >>>>` section.
+///
+/// `start` and `end` are the 1-based line numbers of the `<<<<` and `>>>>`
+/// lines respectively.
 pub struct Block {
     pub label: String,
     pub lines: Vec<String>,
+    pub start: usize,
+    pub end: usize,
 }
 
 pub struct Blocks<R> {
     lines: io::Lines<R>,
+    line_number: usize,
+    pending: Option<(String, usize)>,
+    finished: bool,
 }
 
 impl<R: ReadBlockExt> Iterator for Blocks<R> {
-    type Item = Block;
+    type Item = Result<Block>;
     fn next(&mut self) -> Option<Self::Item> {
-        read_block(&mut self.lines).ok()
+        if self.finished {
+            return None;
+        }
+        let block = self.read_block();
+        if let Some(Err(Error::IO(_))) = block {
+            self.finished = true;
+        }
+        block
     }
 }
 
 pub trait ReadBlockExt: io::BufRead {
     fn blocks(self) -> Blocks<Self> where Self: Sized {
-        Blocks { lines:self.lines() }
+        Blocks {
+            lines: self.lines(),
+            line_number: 0,
+            pending: None,
+            finished: false,
+        }
     }
 }
 
 impl<R: io::BufRead> ReadBlockExt for R {}
 
-fn is_comment(line: &String) -> bool {
-    &line[0..1] == "*"
+/// Formats a `<<<< label ... >>>>` section, with each comment written as a
+/// `**` line right after the label, or an empty comment as a blank line.
+pub struct BlockFormat<'a, C: 'a, L: 'a> {
+    label: &'a str,
+    comments: &'a [C],
+    lines: &'a [L],
+}
+
+pub fn format_block<'a, C, L>(label: &'a str, comments: &'a [C], lines: &'a [L]) -> BlockFormat<'a, C, L>
+    where C: fmt::Display, L: fmt::Display
+{
+    BlockFormat {
+        label,
+        comments,
+        lines,
+    }
+}
+
+impl<'a, C: fmt::Display, L: fmt::Display> fmt::Display for BlockFormat<'a, C, L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "<<<< {}", self.label)?;
+        for comment in self.comments {
+            let comment = comment.to_string();
+            if comment.is_empty() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, "** {}", comment)?;
+            }
+        }
+        for line in self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        writeln!(f, ">>>>")
+    }
+}
+
+pub trait WriteBlockExt: io::Write {
+    fn write_block<L: fmt::Display>(&mut self, label: &str, lines: &[L]) -> io::Result<()> {
+        let comments: &[&str] = &[];
+        self.write_block_with_comments(label, comments, lines)
+    }
+
+    fn write_block_with_comments<C, L>(&mut self, label: &str, comments: &[C], lines: &[L]) -> io::Result<()>
+        where C: fmt::Display, L: fmt::Display
+    {
+        write!(self, "{}", format_block(label, comments, lines))
+    }
 }
 
-fn is_end_of_block(line: &String) -> bool {
-    line.len() >= 4 && &line[0..4] == ">>>>"
+impl<W: io::Write> WriteBlockExt for W {}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with('*')
 }
 
-fn search_start_of_block<R: io::BufRead>(lines: &mut io::Lines<R>) -> io::Result<String> {
-    for line in lines {
-        let line = line?;
-        if line.is_empty() || is_comment(&line) { continue; }
-        if &line[0..4] == "<<<<" {
-            return Ok(line[4..].trim().to_string());
+fn is_end_of_block(line: &str) -> bool {
+    line.starts_with(">>>>")
+}
+
+fn start_of_block(line: &str) -> Option<String> {
+    line.strip_prefix("<<<<").map(|label| label.trim().to_string())
+}
+
+impl<R: io::BufRead> Blocks<R> {
+    fn next_line(&mut self) -> Option<io::Result<String>> {
+        let line = self.lines.next();
+        if line.is_some() {
+            self.line_number += 1;
+        }
+        line
+    }
+
+    fn search_start_of_block(&mut self) -> Option<Result<(String, usize)>> {
+        while let Some(line) = self.next_line() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            if line.is_empty() || is_comment(&line) { continue; }
+            if let Some(label) = start_of_block(&line) {
+                return Some(Ok((label, self.line_number)));
+            }
+            // skip
+        }
+        None
+    }
+
+    fn read_block(&mut self) -> Option<Result<Block>> {
+        let (label, start) = match self.pending.take() {
+            Some(start) => start,
+            None => match self.search_start_of_block()? {
+                Ok(start) => start,
+                Err(err) => return Some(Err(err)),
+            },
+        };
+
+        let mut contents = Vec::new();
+        while let Some(line) = self.next_line() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            if line.is_empty() || is_comment(&line) { continue; }
+            if is_end_of_block(&line) {
+                return Some(Ok(Block {
+                    label,
+                    lines: contents,
+                    start,
+                    end: self.line_number,
+                }));
+            }
+            if let Some(next) = start_of_block(&line) {
+                // The next block starts before this one is closed.
+                self.pending = Some((next, self.line_number));
+                break;
+            }
+            contents.push(line);
         }
-        // skip
+
+        Some(Err(Error::UnterminatedBlock { label, line: start }))
     }
-    Err(io::Error::new(io::ErrorKind::InvalidData, ""))
 }
 
-fn read_block<R: io::BufRead>(lines: &mut io::Lines<R>) -> io::Result<Block> {
-    let label = search_start_of_block(lines)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::Lcg;
+
+    fn read_all(input: &[u8]) -> Vec<Result<Block>> {
+        input.blocks().collect()
+    }
+
+    #[test]
+    fn test_read_blocks() {
+        let input = b"** comment\n\
+                      <<<< first\n\
+                      a\n\
+                      \n\
+                      ** comment\n\
+                      b\n\
+                      >>>>\n\
+                      outside\n\
+                      <<<< second\n\
+                      >>>>\n";
+        let blocks = read_all(input);
+        assert_eq!(blocks.len(), 2);
+
+        let first = blocks[0].as_ref().unwrap();
+        assert_eq!(first.label, "first");
+        assert_eq!(first.lines, vec!["a", "b"]);
+        assert_eq!(first.start, 2);
+        assert_eq!(first.end, 7);
+
+        let second = blocks[1].as_ref().unwrap();
+        assert_eq!(second.label, "second");
+        assert!(second.lines.is_empty());
+        assert_eq!(second.start, 9);
+        assert_eq!(second.end, 10);
+    }
+
+    #[test]
+    fn test_missing_terminator() {
+        let input = b"<<<< first\na\n<<<< second\nb\n>>>>\n<<<< third\nc\n";
+        let blocks = read_all(input);
+        assert_eq!(blocks.len(), 3);
+
+        match blocks[0] {
+            Err(Error::UnterminatedBlock { ref label, line }) => {
+                assert_eq!(label, "first");
+                assert_eq!(line, 1);
+            }
+            _ => panic!("expected an unterminated block"),
+        }
+
+        let second = blocks[1].as_ref().unwrap();
+        assert_eq!(second.label, "second");
+        assert_eq!(second.lines, vec!["b"]);
+        assert_eq!(second.start, 3);
+        assert_eq!(second.end, 5);
 
-    let mut contents = Vec::new();
-    for line in lines {
-        let line = line?;
-        if line.is_empty() || is_comment(&line) { continue; }
-        if is_end_of_block(&line) { break; }
-        contents.push(line);
+        match blocks[2] {
+            Err(Error::UnterminatedBlock { ref label, line }) => {
+                assert_eq!(label, "third");
+                assert_eq!(line, 6);
+            }
+            _ => panic!("expected an unterminated block"),
+        }
+    }
+
+    #[test]
+    fn test_stops_after_io_error() {
+        let blocks = read_all(b"<<<< first\n\xff\xfe\n>>>>\n<<<< second\n>>>>\n");
+        assert_eq!(blocks.len(), 1);
+        match blocks[0] {
+            Err(Error::IO(_)) => {}
+            _ => panic!("expected an IO error"),
+        }
     }
 
-    Ok(Block { label:label, lines:contents })
+    #[test]
+    fn test_write_blocks() {
+        let mut output = Vec::new();
+        output.write_block_with_comments("first", &["total = 2", ""], &[1, 2]).unwrap();
+        output.write_block("second", &["a b"]).unwrap();
+        assert_eq!(String::from_utf8(output.clone()).unwrap(),
+                   "<<<< first\n** total = 2\n\n1\n2\n>>>>\n<<<< second\na b\n>>>>\n");
+
+        let blocks = read_all(&output);
+        assert_eq!(blocks.len(), 2);
+
+        let first = blocks[0].as_ref().unwrap();
+        assert_eq!(first.label, "first");
+        assert_eq!(first.lines, vec!["1", "2"]);
+
+        let second = blocks[1].as_ref().unwrap();
+        assert_eq!(second.label, "second");
+        assert_eq!(second.lines, vec!["a b"]);
+    }
+
+    #[test]
+    fn test_fuzz_never_panics() {
+        let fragments: &[&[u8]] = &[
+            b"<<<<", b">>>>", b"<<", b">>", b"*", b"**", b" ", b"\n", b"\n",
+            b"\r\n", b"label", b"1", b"\xc3\xa9", b"\xe3\x81\x82", b"\xff",
+        ];
+        let mut rng = Lcg(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..2000 {
+            let mut input = Vec::new();
+            for _ in 0..rng.below(64) {
+                input.extend_from_slice(fragments[rng.below(fragments.len())]);
+            }
+            let num_lines = input.iter().filter(|&&c| c == b'\n').count() + 1;
+
+            let mut last_end = 0;
+            for block in read_all(&input) {
+                match block {
+                    Ok(block) => {
+                        assert!(last_end < block.start);
+                        assert!(block.start < block.end);
+                        assert!(block.end <= num_lines);
+                        last_end = block.end;
+                    }
+                    Err(Error::UnterminatedBlock { line, .. }) => {
+                        assert!(last_end < line);
+                        assert!(line <= num_lines);
+                        last_end = line;
+                    }
+                    Err(Error::IO(_)) => {}
+                    Err(_) => panic!("unexpected error"),
+                }
+            }
+        }
+    }
 }
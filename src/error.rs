@@ -1,4 +1,6 @@
 use std::io;
+use std::fmt;
+use std::error;
 use std::result;
 use std::num::{ParseIntError, ParseFloatError};
 
@@ -7,10 +9,108 @@ pub enum Error {
     ParseIntError(ParseIntError),
     ParseFloatError(ParseFloatError),
     IO(io::Error),
+    /// A block starting at `line` is not closed by `>>>>`.
+    UnterminatedBlock { label: String, line: usize },
+    /// A section required by the caller is missing from an input file.
+    MissingSection(String),
+    /// A required `key = value` parameter is missing from a section.
+    MissingParameter { section: String, key: String },
+    /// A line does not follow the expected format.
+    InvalidLine(String),
+    /// A row has a different number of columns than the header.
+    ColumnCount { expected: usize, found: usize },
+    /// A section has no or several lines starting with a keyword, where
+    /// exactly one is needed.
+    LineCount { keyword: String, found: usize },
+    /// A column requested by the caller is not in a time-series file.
+    UnknownColumn(String),
+    /// Two time-series files have different columns.
+    ColumnMismatch { expected: Vec<String>, found: Vec<String> },
+    /// Two time-series files have different header lines.
+    HeaderMismatch { expected: String, found: String },
+    /// Two time-series files have different units.
+    UnitMismatch { expected: Vec<String>, found: Vec<String> },
+    /// A continuation of a time series starts at or before its first step.
+    NonMonotonicSteps { previous: i32, step: i32 },
+    /// Files written in step with each other have a frame at different steps.
+    StepMismatch { frame: usize, expected: i32, found: i32 },
+    /// A column has no samples to analyse.
+    NoSamples(String),
+    /// A wrong number of files was given, such as one per replica.
+    FileCount { expected: usize, found: usize },
+    /// Time-series and DCD files were given together where one kind is needed.
+    MixedInputs,
+    /// The tempk of a run expected at one temperature varies, as in a
+    /// replica of a replica-exchange run.
+    VaryingTemperature { min: f64, max: f64 },
+    /// A model cannot be fitted to the data, such as with too few points.
+    NoFit(String),
+    /// Trajectories of different numbers of particles were combined.
+    ParticleCount { expected: usize, found: usize },
+    /// A frame beyond the end of a trajectory was requested.
+    FrameIndex { index: usize, num_frames: usize },
+    /// An error at the 1-based line number `line` of a file.
+    AtLine { line: usize, error: Box<Error> },
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+impl Error {
+    /// Attaches the line number at which the error occurred.
+    pub fn at_line(self, line: usize) -> Self {
+        match self {
+            Error::IO(_) | Error::AtLine { .. } => self,
+            _ => Error::AtLine { line, error: Box::new(self) },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ParseIntError(ref err) => write!(f, "{}", err),
+            Error::ParseFloatError(ref err) => write!(f, "{}", err),
+            Error::IO(ref err) => write!(f, "{}", err),
+            Error::UnterminatedBlock { ref label, line } =>
+                write!(f, "block '{}' at line {} is not closed by '>>>>'", label, line),
+            Error::MissingSection(ref name) => write!(f, "missing section '{}'", name),
+            Error::MissingParameter { ref section, ref key } =>
+                write!(f, "missing parameter '{}' in section '{}'", key, section),
+            Error::InvalidLine(ref line) => write!(f, "invalid line '{}'", line),
+            Error::ColumnCount { expected, found } =>
+                write!(f, "expected {} columns, found {}", expected, found),
+            Error::LineCount { ref keyword, found } =>
+                write!(f, "expected one '{}' line, found {}", keyword, found),
+            Error::UnknownColumn(ref name) => write!(f, "no column '{}'", name),
+            Error::ColumnMismatch { ref expected, ref found } =>
+                write!(f, "expected columns '{}', found '{}'", expected.join(" "), found.join(" ")),
+            Error::HeaderMismatch { ref expected, ref found } =>
+                write!(f, "expected header line '{}', found '{}'", expected, found),
+            Error::UnitMismatch { ref expected, ref found } =>
+                write!(f, "expected units '{}', found '{}'", expected.join(" "), found.join(" ")),
+            Error::NonMonotonicSteps { previous, step } =>
+                write!(f, "step {} does not follow step {}", step, previous),
+            Error::StepMismatch { frame, expected, found } =>
+                write!(f, "frame {} is at step {}, not {}", frame, found, expected),
+            Error::NoSamples(ref name) => write!(f, "no samples of '{}'", name),
+            Error::FileCount { expected, found } =>
+                write!(f, "expected {} files, found {}", expected, found),
+            Error::MixedInputs => write!(f, "cannot mix time-series and DCD files"),
+            Error::VaryingTemperature { min, max } =>
+                write!(f, "tempk varies from {:.2} to {:.2} K; demultiplex replica-exchange runs by \
+                           temperature with remd first", min, max),
+            Error::NoFit(ref model) => write!(f, "cannot fit {}", model),
+            Error::ParticleCount { expected, found } =>
+                write!(f, "expected {} particles, found {}", expected, found),
+            Error::FrameIndex { index, num_frames } =>
+                write!(f, "frame {} is out of {} frames", index, num_frames),
+            Error::AtLine { line, ref error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
 impl From<ParseIntError> for Error {
     fn from(err: ParseIntError) -> Self {
         Error::ParseIntError(err)
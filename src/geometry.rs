@@ -0,0 +1,138 @@
+//! Structural observables of the particle positions in a frame.
+
+pub type Vector3d = (f32, f32, f32);
+
+pub fn distance(x: &Vector3d, y: &Vector3d) -> f32 {
+    ((x.0 - y.0).powi(2) + (x.1 - y.1).powi(2) + (x.2 - y.2).powi(2)).sqrt()
+}
+
+fn center(positions: &[Vector3d]) -> (f64, f64, f64) {
+    let n = positions.len() as f64;
+    let sum = positions.iter().fold((0.0, 0.0, 0.0), |sum, x| {
+        (sum.0 + x.0 as f64, sum.1 + x.1 as f64, sum.2 + x.2 as f64)
+    });
+    (sum.0 / n, sum.1 / n, sum.2 / n)
+}
+
+fn centered(positions: &[Vector3d]) -> Vec<[f64; 3]> {
+    let c = center(positions);
+    positions.iter().map(|x| [x.0 as f64 - c.0, x.1 as f64 - c.1, x.2 as f64 - c.2]).collect()
+}
+
+/// Returns the radius of gyration of equally weighted particles.
+pub fn radius_of_gyration(positions: &[Vector3d]) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = centered(positions).iter().map(|x| x[0] * x[0] + x[1] * x[1] + x[2] * x[2]).sum();
+    (sum / positions.len() as f64).sqrt()
+}
+
+/// Returns the largest eigenvalue of a symmetric 4x4 matrix by cyclic
+/// Jacobi rotations.
+fn largest_eigenvalue(mut a: [[f64; 4]; 4]) -> f64 {
+    for _ in 0..50 {
+        let off: f64 = (0..4).flat_map(|i| (0..4).filter(move |&j| j != i).map(move |j| (i, j)))
+                             .map(|(i, j)| a[i][j] * a[i][j])
+                             .sum();
+        if off < 1e-22 {
+            break;
+        }
+        for p in 0..4 {
+            for q in p + 1..4 {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (row_p, row_q) = (a[p], a[q]);
+                for (k, (apk, aqk)) in row_p.iter().zip(&row_q).enumerate() {
+                    a[p][k] = c * apk - s * aqk;
+                    a[q][k] = s * apk + c * aqk;
+                }
+            }
+        }
+    }
+    (0..4).map(|i| a[i][i]).fold(f64::NEG_INFINITY, f64::max)
+}
+
+/// Returns the root-mean-square deviation of `positions` from `reference`
+/// after optimal superposition, by the quaternion method of Horn (1987).
+pub fn rmsd(positions: &[Vector3d], reference: &[Vector3d]) -> f64 {
+    let n = positions.len().min(reference.len());
+    if n == 0 {
+        return 0.0;
+    }
+    let x = centered(&positions[..n]);
+    let y = centered(&reference[..n]);
+
+    let mut s = [[0.0; 3]; 3];
+    let mut norms = 0.0;
+    for (x, y) in x.iter().zip(&y) {
+        for a in 0..3 {
+            norms += x[a] * x[a] + y[a] * y[a];
+            for b in 0..3 {
+                s[a][b] += x[a] * y[b];
+            }
+        }
+    }
+
+    let k = [
+        [s[0][0] + s[1][1] + s[2][2], s[1][2] - s[2][1], s[2][0] - s[0][2], s[0][1] - s[1][0]],
+        [s[1][2] - s[2][1], s[0][0] - s[1][1] - s[2][2], s[0][1] + s[1][0], s[2][0] + s[0][2]],
+        [s[2][0] - s[0][2], s[0][1] + s[1][0], -s[0][0] + s[1][1] - s[2][2], s[1][2] + s[2][1]],
+        [s[0][1] - s[1][0], s[2][0] + s[0][2], s[1][2] + s[2][1], -s[0][0] - s[1][1] + s[2][2]],
+    ];
+    let deviation = norms - 2.0 * largest_eigenvalue(k);
+    (deviation.max(0.0) / n as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: [Vector3d; 5] = [(0.0, 0.0, 0.0), (3.8, 0.0, 0.0), (5.0, 3.6, 0.0),
+                                      (4.0, 5.0, 3.0), (1.0, 6.0, 4.5)];
+
+    #[test]
+    fn test_radius_of_gyration() {
+        let square = [(1.0, 1.0, 0.0), (-1.0, 1.0, 0.0), (-1.0, -1.0, 0.0), (1.0, -1.0, 0.0)];
+        assert!((radius_of_gyration(&square) - 2f64.sqrt()).abs() < 1e-12);
+        assert_eq!(radius_of_gyration(&[]), 0.0);
+        assert_eq!(distance(&(0.0, 3.0, 0.0), &(4.0, 0.0, 0.0)), 5.0);
+    }
+
+    #[test]
+    fn test_rmsd_of_superposable_structures() {
+        // Rotated by 90 degrees about z, then by 60 degrees about x, and shifted.
+        let (sin, cos) = (60f32.to_radians().sin(), 60f32.to_radians().cos());
+        let moved: Vec<_> = POSITIONS.iter().map(|&(x, y, z)| {
+            let (x, y) = (-y, x);
+            let (y, z) = (cos * y - sin * z, sin * y + cos * z);
+            (x + 10.0, y - 4.0, z + 2.5)
+        }).collect();
+        assert!(rmsd(&moved, &POSITIONS) < 1e-3);
+        assert!(rmsd(&POSITIONS, &POSITIONS) < 1e-6);
+    }
+
+    #[test]
+    fn test_rmsd_of_different_structures() {
+        let mut moved = POSITIONS.to_vec();
+        moved[4].2 += 2.0;
+        let value = rmsd(&moved, &POSITIONS);
+        // Superposition can only reduce the plain deviation of 2 / sqrt(5).
+        assert!(value > 0.1 && value < 2.0 / 5f64.sqrt(), "rmsd = {}", value);
+
+        // A mirror image cannot be superposed by a rotation.
+        let mirrored: Vec<_> = POSITIONS.iter().map(|&(x, y, z)| (x, y, -z)).collect();
+        assert!(rmsd(&mirrored, &POSITIONS) > 0.1);
+    }
+}
@@ -0,0 +1,181 @@
+//! Histograms and free-energy profiles of time-series columns.
+
+use std::f64;
+
+/// The Boltzmann constant in kcal/(mol K), the energy unit of CafeMol.
+pub const BOLTZMANN: f64 = 1.987_204e-3;
+
+/// Evenly spaced bins over `[min, max]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Axis {
+    pub min: f64,
+    pub max: f64,
+    pub num_bins: usize,
+}
+
+impl Axis {
+    pub fn new(min: f64, max: f64, num_bins: usize) -> Self {
+        Axis { min, max, num_bins }
+    }
+
+    /// Returns an axis spanning `samples`, or `None` if there are none.
+    pub fn spanning(samples: &[f64], num_bins: usize) -> Option<Self> {
+        let min = samples.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = samples.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if min.is_finite() && max.is_finite() {
+            Some(Axis::new(min, max, num_bins))
+        } else {
+            None
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        (self.max - self.min) / self.num_bins as f64
+    }
+
+    /// Returns the bin of `x`; `max` itself falls in the last bin.
+    pub fn index(&self, x: f64) -> Option<usize> {
+        if !(x >= self.min && x <= self.max) || self.num_bins == 0 {
+            return None;
+        }
+        if self.max == self.min {
+            return Some(0);
+        }
+        let i = ((x - self.min) / self.width()) as usize;
+        Some(i.min(self.num_bins - 1))
+    }
+
+    pub fn center(&self, i: usize) -> f64 {
+        self.min + (i as f64 + 0.5) * self.width()
+    }
+}
+
+/// A histogram over one or more axes, with bins stored in row-major order.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    pub axes: Vec<Axis>,
+    pub counts: Vec<f64>,
+}
+
+impl Histogram {
+    pub fn new(axes: Vec<Axis>) -> Self {
+        let num_bins = axes.iter().map(|axis| axis.num_bins).product();
+        Histogram {
+            axes,
+            counts: vec![0.0; num_bins],
+        }
+    }
+
+    /// Returns the bin of `point`, or `None` if it is out of range.
+    pub fn index(&self, point: &[f64]) -> Option<usize> {
+        let mut index = 0;
+        for (axis, &x) in self.axes.iter().zip(point) {
+            index = index * axis.num_bins + axis.index(x)?;
+        }
+        Some(index)
+    }
+
+    /// Returns the centers of the bin `index` along each axis.
+    pub fn centers(&self, mut index: usize) -> Vec<f64> {
+        let mut centers = vec![0.0; self.axes.len()];
+        for (center, axis) in centers.iter_mut().zip(&self.axes).rev() {
+            *center = axis.center(index % axis.num_bins);
+            index /= axis.num_bins;
+        }
+        centers
+    }
+
+    /// Counts `point` with `weight`, returning false if it is out of range.
+    pub fn add_weighted(&mut self, point: &[f64], weight: f64) -> bool {
+        match self.index(point) {
+            Some(i) => {
+                self.counts[i] += weight;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn add(&mut self, point: &[f64]) -> bool {
+        self.add_weighted(point, 1.0)
+    }
+
+    pub fn total(&self) -> f64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the probability of each bin.
+    pub fn probabilities(&self) -> Vec<f64> {
+        let total = self.total();
+        self.counts.iter().map(|&count| if total > 0.0 { count / total } else { 0.0 }).collect()
+    }
+
+    /// Returns the free energy `-kT ln P` of each bin at `temperature` in
+    /// kcal/mol, shifted so that its minimum is zero, or `None` for an
+    /// empty bin.
+    pub fn free_energy(&self, temperature: f64) -> Vec<Option<f64>> {
+        let kt = BOLTZMANN * temperature;
+        let max = self.counts.iter().cloned().fold(0.0, f64::max);
+        self.counts.iter().map(|&count| {
+            if count > 0.0 {
+                Some(kt * (max / count).ln())
+            } else {
+                None
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_axis() {
+        let axis = Axis::new(0.0, 1.0, 4);
+        assert_eq!(axis.width(), 0.25);
+        assert_eq!(axis.index(0.0), Some(0));
+        assert_eq!(axis.index(0.3), Some(1));
+        assert_eq!(axis.index(1.0), Some(3));
+        assert_eq!(axis.index(1.01), None);
+        assert_eq!(axis.index(-0.01), None);
+        assert_eq!(axis.index(f64::NAN), None);
+        assert_eq!(axis.center(1), 0.375);
+
+        let axis = Axis::spanning(&[0.5, -1.0, 2.0], 3).unwrap();
+        assert_eq!(axis, Axis::new(-1.0, 2.0, 3));
+        assert!(Axis::spanning(&[], 3).is_none());
+    }
+
+    #[test]
+    fn test_histogram_2d() {
+        let mut histogram = Histogram::new(vec![Axis::new(0.0, 1.0, 2), Axis::new(0.0, 3.0, 3)]);
+        assert!(histogram.add(&[0.2, 2.5]));
+        assert!(histogram.add(&[0.2, 2.9]));
+        assert!(histogram.add(&[0.7, 0.1]));
+        assert!(!histogram.add(&[0.7, 3.5]));
+
+        assert_eq!(histogram.counts, vec![0.0, 0.0, 2.0, 1.0, 0.0, 0.0]);
+        assert_eq!(histogram.centers(2), vec![0.25, 2.5]);
+        assert_eq!(histogram.centers(3), vec![0.75, 0.5]);
+        assert_eq!(histogram.total(), 3.0);
+        assert_eq!(histogram.probabilities()[2], 2.0 / 3.0);
+    }
+
+    #[test]
+    fn test_free_energy() {
+        let mut histogram = Histogram::new(vec![Axis::new(0.0, 2.0, 2)]);
+        let temperature = 300.0;
+        let kt = BOLTZMANN * temperature;
+        // A difference of 1 kcal/mol between the two bins.
+        histogram.add_weighted(&[0.5], 1.0);
+        histogram.add_weighted(&[1.5], (-1.0 / kt).exp());
+
+        let free_energy = histogram.free_energy(temperature);
+        assert_eq!(free_energy[0], Some(0.0));
+        assert!((free_energy[1].unwrap() - 1.0).abs() < 1e-12);
+
+        let empty = Histogram::new(vec![Axis::new(0.0, 1.0, 1)]);
+        assert_eq!(empty.free_energy(temperature), vec![None]);
+    }
+}
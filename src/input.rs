@@ -0,0 +1,587 @@
+use error::{Error, Result};
+use block::{format_block, ReadBlockExt};
+use std::str::FromStr;
+use std::fmt;
+
+/// A line of an input section, either `key = value` or anything else
+/// (e.g. the unit table of `unit_and_state`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Param(String, String),
+    Line(String),
+}
+
+impl FromStr for Entry {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        Ok(match line.find('=') {
+            Some(pos) => Entry::Param(line[..pos].trim().to_string(),
+                                      line[pos+1..].trim().to_string()),
+            None => Entry::Line(line.trim().to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Entry::Param(ref key, ref value) => write!(f, "{} = {}", key, value),
+            Entry::Line(ref line) => write!(f, "{}", line),
+        }
+    }
+}
+
+/// A `<<<< name ... >>>>` section of a CafeMol input file.
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub name: String,
+    pub entries: Vec<Entry>,
+}
+
+impl Section {
+    pub fn new(name: &str) -> Self {
+        Section {
+            name: name.to_string(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the value of `key`, compared case-insensitively as CafeMol does.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().filter_map(|entry| match *entry {
+            Entry::Param(ref k, ref v) if k.eq_ignore_ascii_case(key) => Some(v.as_str()),
+            _ => None,
+        }).next()
+    }
+
+    pub fn parse<T>(&self, key: &str) -> Result<Option<T>>
+        where T: FromStr, Error: From<T::Err>
+    {
+        match self.get(key) {
+            Some(value) => Ok(Some(value.parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn require<T>(&self, key: &str) -> Result<T>
+        where T: FromStr, Error: From<T::Err>
+    {
+        self.require_str(key)?.parse().map_err(Error::from)
+    }
+
+    pub fn require_str(&self, key: &str) -> Result<&str> {
+        self.get(key).ok_or_else(|| Error::MissingParameter {
+            section: self.name.clone(),
+            key: key.to_string(),
+        })
+    }
+
+    /// Sets `key` to `value`, appending the parameter if it is not present.
+    pub fn set<T: fmt::Display>(&mut self, key: &str, value: T) {
+        let value = value.to_string();
+        for entry in &mut self.entries {
+            if let Entry::Param(ref k, ref mut v) = *entry {
+                if k.eq_ignore_ascii_case(key) {
+                    *v = value;
+                    return;
+                }
+            }
+        }
+        self.entries.push(Entry::Param(key.to_string(), value));
+    }
+
+    /// Returns the lines which are not `key = value` parameters.
+    pub fn lines(&self) -> Vec<&str> {
+        self.entries.iter().filter_map(|entry| match *entry {
+            Entry::Line(ref line) => Some(line.as_str()),
+            _ => None,
+        }).collect()
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let comments: &[&str] = &[];
+        write!(f, "{}", format_block(&self.name, comments, &self.entries))
+    }
+}
+
+/// The `filenames` section.
+pub struct Filenames {
+    pub path: String,
+    pub filename: String,
+    /// The output types in `OUTPUT` lines, e.g. `dcd` or `pdb`.
+    pub outputs: Vec<String>,
+    pub path_natinfo: Option<String>,
+    /// The `NINFO(...)` lines.
+    pub ninfo: Vec<String>,
+}
+
+impl Filenames {
+    pub fn from_section(section: &Section) -> Result<Self> {
+        let lines = section.lines();
+        Ok(Filenames {
+            path: section.require_str("path")?.to_string(),
+            filename: section.require_str("filename")?.to_string(),
+            outputs: lines.iter()
+                          .filter(|line| starts_with_keyword(line, "OUTPUT"))
+                          .flat_map(|line| line.split_whitespace().skip(1))
+                          .map(|output| output.to_string())
+                          .collect(),
+            path_natinfo: section.get("path_natinfo").map(|path| path.to_string()),
+            ninfo: lines.iter()
+                        .filter(|line| starts_with_keyword(line, "NINFO"))
+                        .map(|line| line.to_string())
+                        .collect(),
+        })
+    }
+
+    /// Returns the path of an output file such as `ts` or `dcd`.
+    pub fn output(&self, extension: &str) -> String {
+        format!("{}/{}.{}", self.path, self.filename, extension)
+    }
+}
+
+/// The `job_cntl` section.
+pub struct JobCntl {
+    pub i_run_mode: i32,
+    pub i_simulate_type: i32,
+    pub i_initial_state: i32,
+}
+
+impl JobCntl {
+    pub fn from_section(section: &Section) -> Result<Self> {
+        Ok(JobCntl {
+            i_run_mode: section.require("i_run_mode")?,
+            i_simulate_type: section.require("i_simulate_type")?,
+            i_initial_state: section.require("i_initial_state")?,
+        })
+    }
+}
+
+/// A row of the unit table, e.g. `1-2 protein 1ubq.pdb`.
+pub struct UnitState {
+    pub first: usize,
+    pub last: usize,
+    pub state: String,
+    pub file: String,
+}
+
+impl FromStr for UnitState {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let invalid = || Error::InvalidLine(line.to_string());
+        let mut columns = line.split_whitespace();
+        let units = columns.next().ok_or_else(invalid)?;
+        let (first, last) = match units.find('-') {
+            Some(pos) => (units[..pos].parse()?, units[pos+1..].parse()?),
+            None => {
+                let unit = units.parse()?;
+                (unit, unit)
+            }
+        };
+        Ok(UnitState {
+            first,
+            last,
+            state: columns.next().ok_or_else(invalid)?.to_string(),
+            file: columns.next().unwrap_or("").to_string(),
+        })
+    }
+}
+
+/// The `unit_and_state` section.
+pub struct UnitAndState {
+    pub i_seq_read_style: i32,
+    pub i_go_native_read_style: i32,
+    pub units: Vec<UnitState>,
+}
+
+impl UnitAndState {
+    pub fn from_section(section: &Section) -> Result<Self> {
+        Ok(UnitAndState {
+            i_seq_read_style: section.require("i_seq_read_style")?,
+            i_go_native_read_style: section.require("i_go_native_read_style")?,
+            units: section.lines()
+                          .into_iter()
+                          .map(|line| line.parse())
+                          .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn num_units(&self) -> usize {
+        self.units.iter().map(|unit| unit.last).max().unwrap_or(0)
+    }
+}
+
+/// An interaction line of `energy_function`, e.g. `NLOCAL(1/2) GO EXV`.
+pub struct Interaction {
+    /// The units in parentheses, e.g. `1/2`.
+    pub units: String,
+    pub potentials: Vec<String>,
+}
+
+/// The `energy_function` section.
+pub struct EnergyFunction {
+    pub local: Vec<Interaction>,
+    pub nonlocal: Vec<Interaction>,
+}
+
+impl EnergyFunction {
+    pub fn from_section(section: &Section) -> Result<Self> {
+        let mut local = Vec::new();
+        let mut nonlocal = Vec::new();
+        for line in section.lines() {
+            let interactions = if starts_with_keyword(line, "LOCAL") {
+                &mut local
+            } else if starts_with_keyword(line, "NLOCAL") {
+                &mut nonlocal
+            } else {
+                continue;
+            };
+            let invalid = || Error::InvalidLine(line.to_string());
+            let open = line.find('(').ok_or_else(invalid)?;
+            let close = line.find(')').ok_or_else(invalid)?;
+            if close < open {
+                return Err(invalid());
+            }
+            interactions.push(Interaction {
+                units: line[open+1..close].trim().to_string(),
+                potentials: line[close+1..].split_whitespace()
+                                           .map(|s| s.to_string())
+                                           .collect(),
+            });
+        }
+        Ok(EnergyFunction {
+            local,
+            nonlocal,
+        })
+    }
+}
+
+/// The `md_information` section.
+pub struct MdInformation {
+    pub n_step_sim: usize,
+    /// `n_tstep(i)` for each of the `n_step_sim` stages.
+    pub n_tstep: Vec<usize>,
+    pub tstep_size: f64,
+    pub n_step_save: usize,
+    pub n_step_rst: Option<usize>,
+    pub tempk: Option<f64>,
+    pub n_seed: Option<i64>,
+}
+
+impl MdInformation {
+    pub fn from_section(section: &Section) -> Result<Self> {
+        let n_step_sim = section.require("n_step_sim")?;
+        Ok(MdInformation {
+            n_step_sim,
+            n_tstep: (1..n_step_sim + 1)
+                .map(|i| section.require(&format!("n_tstep({})", i)))
+                .collect::<Result<_>>()?,
+            tstep_size: section.require("tstep_size")?,
+            n_step_save: section.require("n_step_save")?,
+            n_step_rst: section.parse("n_step_rst")?,
+            tempk: section.parse("tempk")?,
+            n_seed: section.parse("n_seed")?,
+        })
+    }
+
+    pub fn total_steps(&self) -> usize {
+        self.n_tstep.iter().sum()
+    }
+}
+
+/// The `replica` section with the temperatures of `replica_temperature`.
+pub struct Replica {
+    pub n_replica_temp: usize,
+    pub n_step_exchange: usize,
+    pub n_period_prob: Option<usize>,
+    /// `REPLICA(i)` for each of the `n_replica_temp` temperatures.
+    pub temperatures: Vec<f64>,
+}
+
+impl Replica {
+    pub fn from_sections(replica: &Section, temperature: Option<&Section>) -> Result<Self> {
+        let n_replica_temp = replica.require("n_replica_temp")?;
+        let temperatures = match temperature {
+            Some(section) => (1..n_replica_temp + 1)
+                .map(|i| section.require(&format!("REPLICA({})", i)))
+                .collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+        Ok(Replica {
+            n_replica_temp,
+            n_step_exchange: replica.require("n_step_exchange")?,
+            n_period_prob: replica.parse("n_period_prob")?,
+            temperatures,
+        })
+    }
+
+    /// Returns the `replica` and `replica_temperature` sections.
+    pub fn to_sections(&self) -> Vec<Section> {
+        let mut replica = Section::new("replica");
+        replica.set("n_replica_temp", self.n_replica_temp);
+        replica.set("n_step_exchange", self.n_step_exchange);
+        if let Some(n_period_prob) = self.n_period_prob {
+            replica.set("n_period_prob", n_period_prob);
+        }
+        let mut temperature = Section::new("replica_temperature");
+        for (i, t) in self.temperatures.iter().enumerate() {
+            temperature.set(&format!("REPLICA({})", i + 1), format!("{:.2}", t));
+        }
+        vec![replica, temperature]
+    }
+}
+
+/// Returns whether `line` starts with `keyword`, compared case-insensitively
+/// as CafeMol does, followed by anything but a name character.
+pub fn starts_with_keyword(line: &str, keyword: &str) -> bool {
+    line.get(..keyword.len()).is_some_and(|head| head.eq_ignore_ascii_case(keyword))
+        && !line.get(keyword.len()..).is_some_and(|rest| {
+            rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+        })
+}
+
+/// A CafeMol input (.inp) file.
+#[derive(Clone, Debug)]
+pub struct Input {
+    pub sections: Vec<Section>,
+}
+
+impl Input {
+    pub fn load<R: ReadBlockExt>(reader: R) -> Result<Self> {
+        let mut sections = Vec::new();
+        for block in reader.blocks() {
+            let block = block?;
+            sections.push(Section {
+                name: block.label,
+                entries: block.lines.iter()
+                                    .map(|line| line.parse())
+                                    .collect::<Result<_>>()?,
+            });
+        }
+        Ok(Input { sections })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    pub fn section_mut(&mut self, name: &str) -> Option<&mut Section> {
+        self.sections.iter_mut().find(|section| section.name == name)
+    }
+
+    fn require_section(&self, name: &str) -> Result<&Section> {
+        self.section(name).ok_or_else(|| Error::MissingSection(name.to_string()))
+    }
+
+    pub fn filenames(&self) -> Result<Filenames> {
+        Filenames::from_section(self.require_section("filenames")?)
+    }
+
+    pub fn job_cntl(&self) -> Result<JobCntl> {
+        JobCntl::from_section(self.require_section("job_cntl")?)
+    }
+
+    pub fn unit_and_state(&self) -> Result<UnitAndState> {
+        UnitAndState::from_section(self.require_section("unit_and_state")?)
+    }
+
+    pub fn energy_function(&self) -> Result<EnergyFunction> {
+        EnergyFunction::from_section(self.require_section("energy_function")?)
+    }
+
+    pub fn md_information(&self) -> Result<MdInformation> {
+        MdInformation::from_section(self.require_section("md_information")?)
+    }
+
+    /// Returns the replica exchange settings, or `None` for a run without
+    /// a `replica` section.
+    pub fn replica(&self) -> Result<Option<Replica>> {
+        match self.section("replica") {
+            Some(replica) => {
+                let temperature = self.section("replica_temperature");
+                Ok(Some(Replica::from_sections(replica, temperature)?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, section) in self.sections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", section)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "\
+** sample input
+<<<< filenames
+path = ./data
+filename = md
+OUTPUT pdb dcd
+path_pdb = ./pdb
+path_ini = ./pdb
+path_natinfo = ./ninfo
+NINFO(all/all) 1ubq.ninfo
+>>>>
+
+<<<< job_cntl
+i_run_mode = 2
+i_simulate_type = 1
+i_initial_state = 2
+>>>>
+
+<<<< unit_and_state
+i_seq_read_style = 1
+i_go_native_read_style = 1
+1-2   protein   1ubq.pdb
+3     protein   2ci2.pdb
+>>>>
+
+<<<< energy_function
+LOCAL(1-3)     L_GO
+NLOCAL(1-3/1-3)  GO EXV
+i_use_atom_protein = 0
+>>>>
+
+<<<< md_information
+n_step_sim = 2
+n_tstep(1) = 1000000
+n_tstep(2) = 500000
+tstep_size = 0.4
+n_step_save = 1000
+n_step_rst = 10000
+tempk = 300.0
+n_seed = 2
+>>>>
+
+<<<< replica
+n_replica_temp = 3
+n_step_exchange = 1000
+n_period_prob = 10
+>>>>
+
+<<<< replica_temperature
+REPLICA(1) = 300.0
+REPLICA(2) = 310.0
+REPLICA(3) = 320.5
+>>>>
+";
+
+    #[test]
+    fn test_parse_input() {
+        let input = Input::load(INPUT.as_bytes()).unwrap();
+        assert_eq!(input.sections.len(), 7);
+
+        let filenames = input.filenames().unwrap();
+        assert_eq!(filenames.path, "./data");
+        assert_eq!(filenames.filename, "md");
+        assert_eq!(filenames.outputs, vec!["pdb", "dcd"]);
+        assert_eq!(filenames.path_natinfo, Some("./ninfo".to_string()));
+        assert_eq!(filenames.ninfo, vec!["NINFO(all/all) 1ubq.ninfo"]);
+        assert_eq!(filenames.output("ts"), "./data/md.ts");
+
+        let job_cntl = input.job_cntl().unwrap();
+        assert_eq!(job_cntl.i_run_mode, 2);
+        assert_eq!(job_cntl.i_simulate_type, 1);
+        assert_eq!(job_cntl.i_initial_state, 2);
+
+        let unit_and_state = input.unit_and_state().unwrap();
+        assert_eq!(unit_and_state.units.len(), 2);
+        assert_eq!(unit_and_state.units[0].first, 1);
+        assert_eq!(unit_and_state.units[0].last, 2);
+        assert_eq!(unit_and_state.units[0].state, "protein");
+        assert_eq!(unit_and_state.units[0].file, "1ubq.pdb");
+        assert_eq!(unit_and_state.units[1].first, 3);
+        assert_eq!(unit_and_state.units[1].last, 3);
+        assert_eq!(unit_and_state.num_units(), 3);
+
+        let energy_function = input.energy_function().unwrap();
+        assert_eq!(energy_function.local.len(), 1);
+        assert_eq!(energy_function.local[0].units, "1-3");
+        assert_eq!(energy_function.local[0].potentials, vec!["L_GO"]);
+        assert_eq!(energy_function.nonlocal.len(), 1);
+        assert_eq!(energy_function.nonlocal[0].units, "1-3/1-3");
+        assert_eq!(energy_function.nonlocal[0].potentials, vec!["GO", "EXV"]);
+
+        let md_information = input.md_information().unwrap();
+        assert_eq!(md_information.n_step_sim, 2);
+        assert_eq!(md_information.n_tstep, vec![1000000, 500000]);
+        assert_eq!(md_information.total_steps(), 1500000);
+        assert_eq!(md_information.tstep_size, 0.4);
+        assert_eq!(md_information.n_step_save, 1000);
+        assert_eq!(md_information.n_step_rst, Some(10000));
+        assert_eq!(md_information.tempk, Some(300.0));
+        assert_eq!(md_information.n_seed, Some(2));
+
+        let replica = input.replica().unwrap().unwrap();
+        assert_eq!(replica.n_replica_temp, 3);
+        assert_eq!(replica.n_step_exchange, 1000);
+        assert_eq!(replica.n_period_prob, Some(10));
+        assert_eq!(replica.temperatures, vec![300.0, 310.0, 320.5]);
+
+        let sections = replica.to_sections();
+        assert_eq!(sections[1].get("REPLICA(3)"), Some("320.50"));
+        let copy = Replica::from_sections(&sections[0], Some(&sections[1])).unwrap();
+        assert_eq!(copy.n_period_prob, Some(10));
+        assert_eq!(copy.temperatures, replica.temperatures);
+    }
+
+    #[test]
+    fn test_missing_parameter() {
+        let mut input = Input::load(INPUT.as_bytes()).unwrap();
+        input.section_mut("md_information").unwrap()
+             .entries.retain(|entry| match *entry {
+                 Entry::Param(ref key, _) => key != "n_step_save",
+                 _ => true,
+             });
+        match input.md_information() {
+            Err(Error::MissingParameter { ref section, ref key }) => {
+                assert_eq!(section, "md_information");
+                assert_eq!(key, "n_step_save");
+            }
+            _ => panic!("expected a missing parameter"),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_input() {
+        let mut input = Input::load(INPUT.as_bytes()).unwrap();
+        input.section_mut("md_information").unwrap().set("tempk", 350.0);
+        input.section_mut("md_information").unwrap().set("n_seed", 7);
+
+        let reloaded = Input::load(input.to_string().as_bytes()).unwrap();
+        assert_eq!(reloaded.sections.len(), input.sections.len());
+        for (x, y) in reloaded.sections.iter().zip(&input.sections) {
+            assert_eq!(x.name, y.name);
+            assert_eq!(x.entries, y.entries);
+        }
+
+        let md_information = reloaded.md_information().unwrap();
+        assert_eq!(md_information.tempk, Some(350.0));
+        assert_eq!(md_information.n_seed, Some(7));
+    }
+
+    #[test]
+    fn test_starts_with_keyword() {
+        assert!(starts_with_keyword("NINFO(all/all) 1ubq.ninfo", "NINFO"));
+        assert!(starts_with_keyword("ninfo(all/all) 1ubq.ninfo", "NINFO"));
+        assert!(!starts_with_keyword("NINFO_FILE 1ubq.ninfo", "NINFO"));
+        assert!(!starts_with_keyword("NIN", "NINFO"));
+        assert!(!starts_with_keyword("NINFé(all/all)", "NINFO"));
+        assert!(!starts_with_keyword("ÉNINFO", "NINFO"));
+    }
+}
@@ -0,0 +1,191 @@
+//! Folding and unfolding transitions of a reaction coordinate such as Q,
+//! assigned with two cutoffs so that recrossings of a single barrier value
+//! are not counted as transitions.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Unfolded,
+    Folded,
+}
+
+impl State {
+    pub fn other(self) -> Self {
+        match self {
+            State::Unfolded => State::Folded,
+            State::Folded => State::Unfolded,
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            State::Unfolded => write!(f, "unfolded"),
+            State::Folded => write!(f, "folded"),
+        }
+    }
+}
+
+/// The cores of the two states: `value <= unfolded` and `value >= folded`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cutoffs {
+    pub unfolded: f64,
+    pub folded: f64,
+}
+
+impl Cutoffs {
+    pub fn new(unfolded: f64, folded: f64) -> Self {
+        Cutoffs { unfolded, folded }
+    }
+
+    /// Returns the state whose core contains `value`, if any.
+    pub fn core(&self, value: f64) -> Option<State> {
+        if value <= self.unfolded {
+            Some(State::Unfolded)
+        } else if value >= self.folded {
+            Some(State::Folded)
+        } else {
+            None
+        }
+    }
+}
+
+/// A transition from the core of one state to the other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition {
+    pub to: State,
+    /// The last step in the core of the state left.
+    pub exit: i32,
+    /// The first step in the core of `to`.
+    pub entry: i32,
+}
+
+impl Transition {
+    /// Returns the duration of the transition path between the two cores.
+    pub fn path_duration(&self) -> i32 {
+        self.entry - self.exit
+    }
+}
+
+/// A stay in a state, from the entry into its core until the entry into the
+/// core of the other state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dwell {
+    pub state: State,
+    pub start: i32,
+    pub end: i32,
+    /// Whether the trajectory ends before the state is left.
+    pub censored: bool,
+}
+
+impl Dwell {
+    pub fn duration(&self) -> i32 {
+        self.end - self.start
+    }
+}
+
+/// The transitions and dwells of a trajectory.
+#[derive(Clone, Debug)]
+pub struct Kinetics {
+    pub transitions: Vec<Transition>,
+    pub dwells: Vec<Dwell>,
+}
+
+impl Kinetics {
+    /// Assigns each step to the state whose core was visited last.
+    pub fn new(steps: &[i32], values: &[f64], cutoffs: Cutoffs) -> Self {
+        let mut transitions = Vec::new();
+        let mut dwells = Vec::new();
+        // The current state, the entry into its core and the last step in it.
+        let mut current: Option<(State, i32, i32)> = None;
+
+        for (&step, &value) in steps.iter().zip(values) {
+            let core = match cutoffs.core(value) {
+                Some(core) => core,
+                None => continue,
+            };
+            current = match current {
+                None => Some((core, step, step)),
+                Some((state, start, _)) if state == core => Some((state, start, step)),
+                Some((state, start, exit)) => {
+                    transitions.push(Transition { to: core, exit, entry: step });
+                    dwells.push(Dwell { state, start, end: step, censored: false });
+                    Some((core, step, step))
+                }
+            };
+        }
+
+        if let (Some((state, start, _)), Some(&last)) = (current, steps.last()) {
+            dwells.push(Dwell { state, start, end: last, censored: true });
+        }
+        Kinetics { transitions, dwells }
+    }
+
+    /// Returns the durations of the completed dwells in `state`.
+    pub fn dwell_times(&self, state: State) -> Vec<i32> {
+        self.dwells.iter()
+                   .filter(|dwell| dwell.state == state && !dwell.censored)
+                   .map(Dwell::duration)
+                   .collect()
+    }
+
+    /// Returns the durations of the transition paths into `to`.
+    pub fn path_durations(&self, to: State) -> Vec<i32> {
+        self.transitions.iter()
+                        .filter(|transition| transition.to == to)
+                        .map(Transition::path_duration)
+                        .collect()
+    }
+
+    /// Returns the mean first passage time from the core of `from` to the
+    /// core of the other state, or `None` if it was never left.
+    pub fn mean_first_passage_time(&self, from: State) -> Option<f64> {
+        let times = self.dwell_times(from);
+        if times.is_empty() {
+            None
+        } else {
+            Some(times.iter().map(|&time| time as f64).sum::<f64>() / times.len() as f64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cutoffs() {
+        let cutoffs = Cutoffs::new(0.3, 0.7);
+        assert_eq!(cutoffs.core(0.1), Some(State::Unfolded));
+        assert_eq!(cutoffs.core(0.5), None);
+        assert_eq!(cutoffs.core(0.7), Some(State::Folded));
+    }
+
+    #[test]
+    fn test_kinetics() {
+        let steps: Vec<_> = (0..12).map(|i| i * 100).collect();
+        // A recrossing of 0.5 at 300 and 500 does not reach the folded core.
+        let values = [0.5, 0.2, 0.1, 0.6, 0.2, 0.6, 0.65, 0.8, 0.9, 0.4, 0.2, 0.25];
+        let kinetics = Kinetics::new(&steps, &values, Cutoffs::new(0.3, 0.7));
+
+        assert_eq!(kinetics.transitions, vec![
+            Transition { to: State::Folded, exit: 400, entry: 700 },
+            Transition { to: State::Unfolded, exit: 800, entry: 1000 },
+        ]);
+        assert_eq!(kinetics.dwells, vec![
+            Dwell { state: State::Unfolded, start: 100, end: 700, censored: false },
+            Dwell { state: State::Folded, start: 700, end: 1000, censored: false },
+            Dwell { state: State::Unfolded, start: 1000, end: 1100, censored: true },
+        ]);
+
+        assert_eq!(kinetics.path_durations(State::Folded), vec![300]);
+        assert_eq!(kinetics.dwell_times(State::Unfolded), vec![600]);
+        assert_eq!(kinetics.mean_first_passage_time(State::Folded), Some(300.0));
+
+        let never = Kinetics::new(&steps[..3], &values[..3], Cutoffs::new(0.3, 0.7));
+        assert!(never.transitions.is_empty());
+        assert_eq!(never.mean_first_passage_time(State::Unfolded), None);
+    }
+}
@@ -0,0 +1,274 @@
+//! Temperature ladders for replica exchange, designed from the energy
+//! distributions of short test runs so that neighbouring replicas exchange
+//! at a target rate.
+
+use std::f64;
+use histogram::BOLTZMANN;
+use statistics;
+
+/// The mean and variance of the energy at a temperature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnergyDistribution {
+    pub temperature: f64,
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl EnergyDistribution {
+    /// Returns the distribution of `energies` sampled at `temperature`, or
+    /// `None` with fewer than two samples.
+    pub fn new(temperature: f64, energies: &[f64]) -> Option<Self> {
+        if energies.len() < 2 {
+            return None;
+        }
+        Some(EnergyDistribution {
+            temperature,
+            mean: statistics::mean(energies),
+            variance: statistics::variance(energies),
+        })
+    }
+
+    /// Returns the heat capacity `variance / kT^2` in kcal/(mol K).
+    pub fn heat_capacity(&self) -> f64 {
+        self.variance / (BOLTZMANN * self.temperature.powi(2))
+    }
+}
+
+/// Returns `ln(erfc(x))` with a relative error of erfc below 1.2e-7, by the
+/// Chebyshev fit of Numerical Recipes.
+fn log_erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let polynomial = -1.265_512_23 + t * (1.000_023_68 + t * (0.374_091_96 + t * (0.096_784_18
+        + t * (-0.186_288_06 + t * (0.278_868_07 + t * (-1.135_203_98 + t * (1.488_515_87
+        + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let log = t.ln() - z * z + polynomial;
+    if x >= 0.0 { log } else { (2.0 - log.exp()).ln() }
+}
+
+/// Returns the expected Metropolis acceptance `<min(1, exp(x))>` of a
+/// normally distributed `x` with `mean` and standard deviation `sd`.
+fn expected_acceptance(mean: f64, sd: f64) -> f64 {
+    if sd <= 0.0 {
+        return mean.min(0.0).exp();
+    }
+    let sqrt2 = f64::consts::SQRT_2;
+    // P(x >= 0) + E[exp(x); x < 0], with the exponential folded into the
+    // logarithm of erfc so that neither overflows.
+    let accepted = 0.5 * log_erfc(-mean / (sd * sqrt2)).exp();
+    let below = 0.5 * (mean + 0.5 * sd * sd + log_erfc((mean + sd * sd) / (sd * sqrt2))).exp();
+    (accepted + below).min(1.0)
+}
+
+/// Energy distributions at any temperature, interpolated linearly between
+/// those of test runs and extrapolated with the heat capacity at either end.
+#[derive(Clone, Debug)]
+pub struct EnergyModel {
+    distributions: Vec<EnergyDistribution>,
+}
+
+/// The acceptance of the ladders of `EnergyModel::ladder` matches the
+/// target within this tolerance.
+const TOLERANCE: f64 = 1e-6;
+
+impl EnergyModel {
+    /// Returns the model of `distributions` at distinct temperatures, or
+    /// `None` if there are none.
+    pub fn new(mut distributions: Vec<EnergyDistribution>) -> Option<Self> {
+        if distributions.is_empty() {
+            return None;
+        }
+        distributions.sort_by(|a, b| {
+            a.temperature.partial_cmp(&b.temperature).unwrap_or(::std::cmp::Ordering::Equal)
+        });
+        distributions.dedup_by(|a, b| a.temperature == b.temperature);
+        Some(EnergyModel { distributions })
+    }
+
+    /// Returns the interpolated distribution at `temperature`.
+    pub fn at(&self, temperature: f64) -> EnergyDistribution {
+        let (first, last) = (self.distributions[0], self.distributions[self.distributions.len() - 1]);
+        let extrapolate = |d: EnergyDistribution| {
+            let heat_capacity = d.heat_capacity();
+            EnergyDistribution {
+                temperature,
+                mean: d.mean + heat_capacity * (temperature - d.temperature),
+                variance: BOLTZMANN * temperature.powi(2) * heat_capacity,
+            }
+        };
+        if temperature <= first.temperature {
+            return extrapolate(first);
+        }
+        if temperature >= last.temperature {
+            return extrapolate(last);
+        }
+        let i = self.distributions.iter().position(|d| d.temperature > temperature).unwrap_or(1);
+        let (a, b) = (self.distributions[i - 1], self.distributions[i]);
+        let x = (temperature - a.temperature) / (b.temperature - a.temperature);
+        let heat_capacity = a.heat_capacity() + x * (b.heat_capacity() - a.heat_capacity());
+        EnergyDistribution {
+            temperature,
+            mean: a.mean + x * (b.mean - a.mean),
+            variance: BOLTZMANN * temperature.powi(2) * heat_capacity,
+        }
+    }
+
+    /// Returns the expected acceptance ratio of exchanges between replicas
+    /// at `low` and `high`, assuming normal energy distributions.
+    pub fn acceptance(&self, low: f64, high: f64) -> f64 {
+        let (a, b) = (self.at(low), self.at(high));
+        let delta_beta = 1.0 / (BOLTZMANN * low) - 1.0 / (BOLTZMANN * high);
+        // An exchange is accepted with min(1, exp(dbeta (E_low - E_high))).
+        let mean = delta_beta * (a.mean - b.mean);
+        let sd = delta_beta.abs() * (a.variance + b.variance).sqrt();
+        expected_acceptance(mean, sd)
+    }
+
+    /// Returns the temperature above `low` whose acceptance with `low` is
+    /// `acceptance`, or `None` if it is above `limit`.
+    fn next(&self, low: f64, acceptance: f64, limit: f64) -> Option<f64> {
+        if self.acceptance(low, limit) >= acceptance {
+            return None;
+        }
+        let (mut lower, mut upper) = (low, limit);
+        while upper - lower > TOLERANCE * low {
+            let middle = 0.5 * (lower + upper);
+            if self.acceptance(low, middle) >= acceptance {
+                lower = middle;
+            } else {
+                upper = middle;
+            }
+        }
+        Some(0.5 * (lower + upper))
+    }
+
+    /// Returns `min` and the `num_steps` temperatures above it with each
+    /// exchanging with the one below at `acceptance`, or `None` if they
+    /// pass `max`.
+    fn walk(&self, min: f64, max: f64, acceptance: f64, num_steps: usize) -> Option<Vec<f64>> {
+        let mut temperatures = vec![min];
+        for _ in 0..num_steps {
+            let next = self.next(temperatures[temperatures.len() - 1], acceptance, max)?;
+            temperatures.push(next);
+        }
+        Some(temperatures)
+    }
+
+    /// Returns the fewest temperatures from `min` to `max` whose neighbours
+    /// all exchange with at least the `target` acceptance ratio, spaced so
+    /// that every pair has the same acceptance. Returns `None` for a target
+    /// outside `(0, 1)` or `max <= min`.
+    pub fn ladder(&self, min: f64, max: f64, target: f64) -> Option<Vec<f64>> {
+        if !(target > 0.0 && target < 1.0 && max > min && min > 0.0) {
+            return None;
+        }
+        // Stepping up at the target until `max` is within reach gives the
+        // fewest temperatures, with the last pair exchanging more often.
+        let mut ladder = vec![min];
+        while let Some(next) = self.next(ladder[ladder.len() - 1], target, max) {
+            ladder.push(next);
+        }
+        let num_steps = ladder.len() - 1;
+        ladder.push(max);
+
+        // The highest acceptance at which as many steps still reach `max`
+        // spaces the temperatures evenly.
+        let (mut lower, mut upper) = (target, 1.0);
+        while upper - lower > TOLERANCE {
+            let middle = 0.5 * (lower + upper);
+            match self.walk(min, max, middle, num_steps) {
+                Some(mut temperatures) => {
+                    if self.acceptance(temperatures[num_steps], max) >= middle {
+                        lower = middle;
+                        temperatures.push(max);
+                        ladder = temperatures;
+                    } else {
+                        upper = middle;
+                    }
+                }
+                None => upper = middle,
+            }
+        }
+        Some(ladder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dof` harmonic degrees of freedom, whose energy has mean `dof kT / 2`
+    /// and variance `dof (kT)^2 / 2`.
+    fn harmonic(dof: f64, temperature: f64) -> EnergyDistribution {
+        let kt = BOLTZMANN * temperature;
+        EnergyDistribution { temperature, mean: 0.5 * dof * kt, variance: 0.5 * dof * kt * kt }
+    }
+
+    #[test]
+    fn test_log_erfc() {
+        for &(x, erfc) in &[(0.0, 1.0), (0.5, 0.479_500_122), (2.0, 0.004_677_735), (-1.0, 1.842_700_793)] {
+            let value: f64 = log_erfc(x).exp();
+            assert!((value - erfc).abs() < 1e-6 * erfc, "erfc({}) = {}", x, value);
+        }
+        assert!((log_erfc(10.0) - 2.088_487_583_762_545e-45f64.ln()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_expected_acceptance() {
+        assert_eq!(expected_acceptance(-1.0, 0.0), (-1f64).exp());
+        assert_eq!(expected_acceptance(1.0, 0.0), 1.0);
+        // By integrating min(1, exp(x)) over the normal density numerically.
+        let (mean, sd) = (-2.0, 1.5);
+        let n = 200_000;
+        let numeric: f64 = (0..n).map(|i| {
+            let x = mean - 10.0 * sd + 20.0 * sd * (i as f64 + 0.5) / n as f64;
+            let density = (-(x - mean).powi(2) / (2.0 * sd * sd)).exp() / (sd * (2.0 * f64::consts::PI).sqrt());
+            x.min(0.0).exp() * density * 20.0 * sd / n as f64
+        }).sum();
+        assert!((expected_acceptance(mean, sd) - numeric).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_energy_model() {
+        let model = EnergyModel::new(vec![harmonic(100.0, 350.0), harmonic(100.0, 300.0)]).unwrap();
+        for &t in &[280.0, 320.0, 400.0] {
+            let d = model.at(t);
+            let expected = harmonic(100.0, t);
+            assert!((d.mean - expected.mean).abs() < 1e-9, "<E>({}) = {}", t, d.mean);
+            assert!((d.variance - expected.variance).abs() < 1e-9);
+        }
+        assert_eq!(model.acceptance(300.0, 300.0), 1.0);
+        assert!(model.acceptance(300.0, 310.0) > model.acceptance(300.0, 320.0));
+        assert!(EnergyModel::new(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_ladder() {
+        let model = EnergyModel::new(vec![harmonic(1000.0, 300.0)]).unwrap();
+        let ladder = model.ladder(300.0, 400.0, 0.3).unwrap();
+        assert_eq!(ladder[0], 300.0);
+        assert_eq!(*ladder.last().unwrap(), 400.0);
+        let acceptances: Vec<_> = ladder.windows(2).map(|pair| model.acceptance(pair[0], pair[1])).collect();
+        assert!(acceptances.iter().all(|&a| a >= 0.3 - 1e-4), "{:?}", acceptances);
+        // The spacing is even in acceptance.
+        let spread = acceptances.iter().cloned().fold(0.0, f64::max)
+                     - acceptances.iter().cloned().fold(1.0, f64::min);
+        assert!(spread < 1e-3, "{:?}", acceptances);
+
+        // Stepping up at the target, as many replicas as the ladder reach
+        // `max` and one more passes it, while with one replica fewer the
+        // last pair exchanges below the target.
+        let steps = ladder.len() - 2;
+        let same = model.walk(300.0, 400.0, 0.3, steps).unwrap();
+        assert!(model.acceptance(same[steps], 400.0) >= 0.3);
+        assert!(model.walk(300.0, 400.0, 0.3, steps + 1).is_none());
+        let fewer = model.walk(300.0, 400.0, 0.3, steps - 1).unwrap();
+        assert!(model.acceptance(fewer[steps - 1], 400.0) < 0.3);
+
+        // More degrees of freedom need more replicas.
+        let larger = EnergyModel::new(vec![harmonic(4000.0, 300.0)]).unwrap();
+        assert!(larger.ladder(300.0, 400.0, 0.3).unwrap().len() > ladder.len());
+        assert_eq!(model.ladder(300.0, 301.0, 0.3).unwrap(), vec![300.0, 301.0]);
+        assert!(model.ladder(300.0, 400.0, 1.0).is_none());
+    }
+}
@@ -2,6 +2,22 @@ pub mod error;
 pub mod time_series;
 pub mod native_info;
 pub mod block;
+pub mod input;
+pub mod sweep;
+pub mod statistics;
+pub mod histogram;
+pub mod wham;
+pub mod replica;
+pub mod kinetics;
+pub mod rates;
+pub mod geometry;
+pub mod melting;
+pub mod npy;
+pub mod ladder;
+pub mod trajectory;
+pub mod args;
+
+mod random;
 
 use std::io::prelude::*;
 
@@ -0,0 +1,318 @@
+//! Two-state fits of melting curves, such as the mean qscore or the folded
+//! fraction of runs at several temperatures.
+
+use std::f64;
+use histogram::BOLTZMANN;
+
+/// An average at a temperature, with its standard error if known.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub temperature: f64,
+    pub value: f64,
+    pub error: Option<f64>,
+}
+
+/// The value of a pure state, linear in the temperature.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Baseline {
+    pub intercept: f64,
+    pub slope: f64,
+}
+
+impl Baseline {
+    pub fn at(&self, temperature: f64) -> f64 {
+        self.intercept + self.slope * temperature
+    }
+}
+
+/// A two-state model whose folded fraction is
+/// `1 / (1 + exp(dH / R (1 / Tm - 1 / T)))`, with the unfolding enthalpy
+/// `dH` in kcal/mol.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TwoState {
+    pub enthalpy: f64,
+    pub melting_temperature: f64,
+    pub folded: Baseline,
+    pub unfolded: Baseline,
+}
+
+/// The maximum number of Levenberg-Marquardt iterations and the relative
+/// change of the residual at which they stop.
+const MAX_ITERATIONS: usize = 1000;
+const TOLERANCE: f64 = 1e-12;
+
+/// The number of parameters of a fit with and without baseline slopes.
+const NUM_PARAMETERS: usize = 6;
+const NUM_FLAT_PARAMETERS: usize = 4;
+
+impl TwoState {
+    fn from_parameters(p: &[f64]) -> Self {
+        let slope = |i: usize| p.get(i).cloned().unwrap_or(0.0);
+        TwoState {
+            enthalpy: p[0],
+            melting_temperature: p[1],
+            folded: Baseline { intercept: p[2], slope: slope(4) },
+            unfolded: Baseline { intercept: p[3], slope: slope(5) },
+        }
+    }
+
+    pub fn folded_fraction(&self, temperature: f64) -> f64 {
+        let exponent = self.enthalpy / BOLTZMANN * (1.0 / self.melting_temperature - 1.0 / temperature);
+        1.0 / (1.0 + exponent.exp())
+    }
+
+    pub fn value(&self, temperature: f64) -> f64 {
+        let fraction = self.folded_fraction(temperature);
+        fraction * self.folded.at(temperature) + (1.0 - fraction) * self.unfolded.at(temperature)
+    }
+
+    /// Returns the derivatives of `value` by the parameters of
+    /// `from_parameters`.
+    fn gradient(&self, temperature: f64, num_parameters: usize) -> Vec<f64> {
+        let fraction = self.folded_fraction(temperature);
+        let inverse = 1.0 / self.melting_temperature - 1.0 / temperature;
+        // The derivative of the value by the exponent of the folded fraction.
+        let d = -fraction * (1.0 - fraction) * (self.folded.at(temperature) - self.unfolded.at(temperature));
+        let gradient = [
+            d * inverse / BOLTZMANN,
+            -d * self.enthalpy / (BOLTZMANN * self.melting_temperature.powi(2)),
+            fraction,
+            1.0 - fraction,
+            fraction * temperature,
+            (1.0 - fraction) * temperature,
+        ];
+        gradient[..num_parameters].to_vec()
+    }
+
+    /// Returns initial parameters from the values at the lowest and highest
+    /// temperatures and the steepest change between neighbouring points.
+    fn guess(points: &[Point]) -> Option<Self> {
+        let mut sorted = points.to_vec();
+        sorted.sort_by(|a, b| a.temperature.partial_cmp(&b.temperature).unwrap_or(::std::cmp::Ordering::Equal));
+        let (first, last) = (sorted.first()?, sorted.last()?);
+        let steepest = sorted.windows(2)
+                             .filter(|pair| pair[1].temperature > pair[0].temperature)
+                             .max_by(|a, b| {
+                                 let slope = |pair: &[Point]| ((pair[1].value - pair[0].value)
+                                                               / (pair[1].temperature - pair[0].temperature)).abs();
+                                 slope(a).partial_cmp(&slope(b)).unwrap_or(::std::cmp::Ordering::Equal)
+                             })?;
+        let melting_temperature = 0.5 * (steepest[0].temperature + steepest[1].temperature);
+        let slope = (steepest[1].value - steepest[0].value) / (steepest[1].temperature - steepest[0].temperature);
+        let amplitude = first.value - last.value;
+        if amplitude == 0.0 {
+            return None;
+        }
+        // At the midpoint the slope is -dH (Af - Au) / (4 R Tm^2).
+        let enthalpy = (-4.0 * BOLTZMANN * melting_temperature.powi(2) * slope / amplitude).max(1.0);
+        Some(TwoState {
+            enthalpy,
+            melting_temperature,
+            folded: Baseline { intercept: first.value, slope: 0.0 },
+            unfolded: Baseline { intercept: last.value, slope: 0.0 },
+        })
+    }
+
+    /// Returns the weighted sum of squared residuals of `points`.
+    fn chi_square(&self, points: &[Point], weights: &[f64]) -> f64 {
+        points.iter()
+              .zip(weights)
+              .map(|(point, w)| w * (point.value - self.value(point.temperature)).powi(2))
+              .sum()
+    }
+
+    /// Fits the model to `points` by Levenberg-Marquardt, weighting them by
+    /// their errors if all are known and positive. With `sloped`, the
+    /// baselines may depend on the temperature.
+    ///
+    /// Returns `None` with no more points than parameters or with the same
+    /// value at the lowest and highest temperatures.
+    pub fn fit(points: &[Point], sloped: bool) -> Option<TwoStateFit> {
+        let num_parameters = if sloped { NUM_PARAMETERS } else { NUM_FLAT_PARAMETERS };
+        if points.len() <= num_parameters {
+            return None;
+        }
+        let weights: Vec<_> = if points.iter().all(|point| point.error.is_some_and(|error| error > 0.0)) {
+            points.iter().map(|point| point.error.map_or(1.0, |error| 1.0 / (error * error))).collect()
+        } else {
+            vec![1.0; points.len()]
+        };
+
+        let guess = TwoState::guess(points)?;
+        let mut parameters = vec![guess.enthalpy, guess.melting_temperature,
+                                  guess.folded.intercept, guess.unfolded.intercept, 0.0, 0.0];
+        parameters.truncate(num_parameters);
+        let mut model = TwoState::from_parameters(&parameters);
+        let mut chi_square = model.chi_square(points, &weights);
+        let mut lambda = 1e-3;
+        let mut iterations = 0;
+        while iterations < MAX_ITERATIONS {
+            iterations += 1;
+            let (curvature, gradient) = normal_equations(&model, points, &weights, num_parameters);
+
+            let mut improved = None;
+            while lambda < 1e12 {
+                let mut damped = curvature.clone();
+                for (i, row) in damped.iter_mut().enumerate() {
+                    row[i] += lambda * curvature[i][i].max(f64::MIN_POSITIVE);
+                }
+                if let Some(step) = solve(damped, gradient.clone()) {
+                    let trial: Vec<_> = parameters.iter().zip(&step).map(|(p, dp)| p + dp).collect();
+                    let trial_model = TwoState::from_parameters(&trial);
+                    let trial_chi_square = trial_model.chi_square(points, &weights);
+                    if trial_chi_square <= chi_square {
+                        improved = Some((trial, trial_model, trial_chi_square));
+                        lambda *= 0.1;
+                        break;
+                    }
+                }
+                lambda *= 10.0;
+            }
+
+            let (trial, trial_model, trial_chi_square) = match improved {
+                Some(improved) => improved,
+                None => break,
+            };
+            let change = chi_square - trial_chi_square;
+            parameters = trial;
+            model = trial_model;
+            chi_square = trial_chi_square;
+            if change <= TOLERANCE * chi_square.max(f64::MIN_POSITIVE) {
+                break;
+            }
+        }
+
+        // The covariance is scaled by the reduced chi-square, so that the
+        // errors reflect the scatter of the points about the fit.
+        let (curvature, _) = normal_equations(&model, points, &weights, num_parameters);
+        let scale = chi_square / (points.len() - num_parameters) as f64;
+        let errors: Vec<_> = (0..num_parameters).map(|i| {
+            let mut unit = vec![0.0; num_parameters];
+            unit[i] = 1.0;
+            solve(curvature.clone(), unit).map_or(f64::NAN, |column| (column[i] * scale).sqrt())
+        }).collect();
+
+        Some(TwoStateFit {
+            model,
+            errors: TwoState::from_parameters(&errors),
+            chi_square,
+            iterations,
+        })
+    }
+}
+
+/// A two-state model fitted to a melting curve.
+#[derive(Clone, Debug)]
+pub struct TwoStateFit {
+    pub model: TwoState,
+    /// The standard error of each parameter of the model; the slopes of a
+    /// fit with flat baselines have none.
+    pub errors: TwoState,
+    pub chi_square: f64,
+    pub iterations: usize,
+}
+
+/// Returns `J^T W J` and `J^T W r` of the residuals `r` of `points`.
+fn normal_equations(model: &TwoState, points: &[Point], weights: &[f64],
+                    num_parameters: usize) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut curvature = vec![vec![0.0; num_parameters]; num_parameters];
+    let mut gradient = vec![0.0; num_parameters];
+    for (point, &w) in points.iter().zip(weights) {
+        let derivatives = model.gradient(point.temperature, num_parameters);
+        let residual = point.value - model.value(point.temperature);
+        for (i, di) in derivatives.iter().enumerate() {
+            gradient[i] += w * di * residual;
+            for (j, dj) in derivatives.iter().enumerate() {
+                curvature[i][j] += w * di * dj;
+            }
+        }
+    }
+    (curvature, gradient)
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting, or
+/// returns `None` if `a` is singular.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for k in 0..n {
+        let pivot = (k..n).max_by(|&i, &j| a[i][k].abs().partial_cmp(&a[j][k].abs())
+                                                 .unwrap_or(::std::cmp::Ordering::Equal))?;
+        if a[pivot][k] == 0.0 || !a[pivot][k].is_finite() {
+            return None;
+        }
+        a.swap(k, pivot);
+        b.swap(k, pivot);
+        let (upper, lower) = a.split_at_mut(k + 1);
+        let row_k = &upper[k];
+        for (i, row) in lower.iter_mut().enumerate() {
+            let factor = row[k] / row_k[k];
+            for (x, y) in row[k..].iter_mut().zip(&row_k[k..]) {
+                *x -= factor * y;
+            }
+            b[k + 1 + i] -= factor * b[k];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for k in (0..n).rev() {
+        let sum: f64 = (k + 1..n).map(|j| a[k][j] * x[j]).sum();
+        x[k] = (b[k] - sum) / a[k][k];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: TwoState = TwoState {
+        enthalpy: 60.0,
+        melting_temperature: 330.0,
+        folded: Baseline { intercept: 1.4, slope: -0.0015 },
+        unfolded: Baseline { intercept: 0.5, slope: -0.001 },
+    };
+
+    fn points(model: &TwoState, noise: f64) -> Vec<Point> {
+        (0..25).map(|i| {
+            let temperature = 300.0 + 2.5 * i as f64;
+            // A deterministic zigzag in place of random noise.
+            let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            Point { temperature, value: model.value(temperature) + sign * noise, error: None }
+        }).collect()
+    }
+
+    #[test]
+    fn test_two_state_model() {
+        assert!((MODEL.folded_fraction(330.0) - 0.5).abs() < 1e-12);
+        assert!(MODEL.folded_fraction(300.0) > 0.95);
+        assert!(MODEL.folded_fraction(360.0) < 0.05);
+        let expected = 0.5 * (MODEL.folded.at(330.0) + MODEL.unfolded.at(330.0));
+        assert!((MODEL.value(330.0) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fit_exact_curve() {
+        let fit = TwoState::fit(&points(&MODEL, 0.0), true).unwrap();
+        assert!((fit.model.enthalpy - MODEL.enthalpy).abs() < 1e-4, "dH = {}", fit.model.enthalpy);
+        assert!((fit.model.melting_temperature - MODEL.melting_temperature).abs() < 1e-5);
+        assert!((fit.model.folded.slope - MODEL.folded.slope).abs() < 1e-7);
+        assert!((fit.model.unfolded.intercept - MODEL.unfolded.intercept).abs() < 1e-5);
+        assert!(fit.chi_square < 1e-16);
+    }
+
+    #[test]
+    fn test_fit_noisy_curve() {
+        let fit = TwoState::fit(&points(&MODEL, 0.01), true).unwrap();
+        let (model, errors) = (fit.model, fit.errors);
+        assert!((model.melting_temperature - 330.0).abs() < 3.0 * errors.melting_temperature + 0.1,
+                "Tm = {} +- {}", model.melting_temperature, errors.melting_temperature);
+        assert!(errors.melting_temperature > 0.0 && errors.melting_temperature < 1.0);
+        assert!((model.enthalpy - 60.0).abs() < 3.0 * errors.enthalpy + 1.0,
+                "dH = {} +- {}", model.enthalpy, errors.enthalpy);
+
+        let flat = TwoState::fit(&points(&MODEL, 0.01), false).unwrap();
+        assert_eq!(flat.model.folded.slope, 0.0);
+        assert_eq!(flat.errors.unfolded.slope, 0.0);
+        assert!(flat.chi_square > fit.chi_square);
+        assert!(TwoState::fit(&points(&MODEL, 0.0)[..6], true).is_none());
+    }
+}
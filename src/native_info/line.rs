@@ -2,23 +2,74 @@ use super::*;
 use std::str::FromStr;
 use std::fmt;
 
+/// A cursor over the fixed-width columns of a ninfo line.
+///
+/// CafeMol writes ninfo records in fixed-width columns, but a value that does
+/// not fit its column (e.g. more than 999999 particles) widens it and shifts
+/// every following column. The cursor detects such run-together columns and
+/// records it in `overflowed`; the line then has to be re-read with
+/// `LineCursor::whitespace`, which splits it into whitespace-separated tokens
+/// instead.
 struct LineCursor<'a> {
     line: &'a str,
     pos: usize,
+    tokenized: bool,
+    overflowed: bool,
 }
 
 impl<'a> LineCursor<'a> {
-    pub fn new(line: &str) -> LineCursor {
+    pub fn new(line: &'a str) -> LineCursor<'a> {
         LineCursor {
             line: line,
             pos: 0,
+            tokenized: false,
+            overflowed: false,
         }
     }
 
-    pub fn proceed(&mut self, len: usize) -> &str {
+    pub fn whitespace(line: &'a str) -> LineCursor<'a> {
+        LineCursor {
+            tokenized: true,
+            ..LineCursor::new(line)
+        }
+    }
+
+    fn is_space_at(&self, pos: usize) -> bool {
+        match self.line.as_bytes().get(pos) {
+            Some(c) => c.is_ascii_whitespace(),
+            None => true,
+        }
+    }
+
+    pub fn proceed(&mut self, len: usize) -> &'a str {
+        if self.tokenized {
+            let rest = &self.line[self.pos..];
+            let start = self.pos + (rest.len() - rest.trim_start().len());
+            let end = self.line[start..]
+                .find(char::is_whitespace)
+                .map_or(self.line.len(), |i| start + i);
+            self.pos = end;
+            return &self.line[start..end];
+        }
+
         let start = self.pos;
         self.pos += len;
-        &self.line[start..self.pos].trim()
+        if self.pos > start && !self.is_space_at(self.pos - 1) && !self.is_space_at(self.pos) {
+            self.overflowed = true;
+        }
+        self.line.get(start..self.pos.min(self.line.len())).unwrap_or("").trim()
+    }
+
+    pub fn skip(&mut self, len: usize) {
+        if self.tokenized {
+            return;
+        }
+        for pos in self.pos..(self.pos + len) {
+            if !self.is_space_at(pos) {
+                self.overflowed = true;
+            }
+        }
+        self.pos += len;
     }
 
     pub fn parse<T: Parsable>(&mut self) -> Result<T, T::Err> {
@@ -26,11 +77,24 @@ impl<'a> LineCursor<'a> {
     }
 
     pub fn parse_with_space<T: Parsable>(&mut self) -> Result<T, T::Err> {
-        self.proceed(1);
+        self.skip(1);
         self.parse()
     }
 }
 
+/// Parses the columns following `keyword` with `f`, falling back to
+/// whitespace tokenisation when the fixed-width columns have run together.
+fn parse_line<T, F>(line: &str, keyword: &str, f: F) -> Result<T, error::Error>
+    where F: Fn(&mut LineCursor) -> Result<T, error::Error>
+{
+    let columns = line.get(keyword.len()..).unwrap_or("");
+    let mut cursor = LineCursor::new(columns);
+    match f(&mut cursor) {
+        Ok(value) if !cursor.overflowed => Ok(value),
+        _ => f(&mut LineCursor::whitespace(columns)),
+    }
+}
+
 trait Parsable: Sized {
     type Err;
     fn parse_from(cursor: &mut LineCursor) -> Result<Self, Self::Err>;
@@ -213,6 +277,24 @@ fn write_with_space<T: Formattable>(f: &mut fmt::Formatter, value: T) -> fmt::Re
     write(f, value)
 }
 
+struct Column<T>(T);
+
+impl<T: Formattable> fmt::Display for Column<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.format(f)
+    }
+}
+
+/// Writes a column directly after the previous one, widening it by a space
+/// when the value fills its width so that the two never run together.
+fn write_packed<T: Formattable>(f: &mut fmt::Formatter, value: T) -> fmt::Result {
+    let column = Column(value).to_string();
+    if !column.starts_with(char::is_whitespace) {
+        write!(f, " ")?;
+    }
+    write!(f, "{}", column)
+}
+
 #[derive(Clone)]
 pub struct Bond {
     pub index:       usize,
@@ -228,15 +310,16 @@ impl FromStr for Bond {
     type Err = error::Error;
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        let mut cursor = LineCursor::new(&line[4..]);
-        Ok(Bond {
-            index:       cursor.parse_with_space()?,
-            pair:        cursor.parse_with_space()?,
-            length:      cursor.parse_with_space()?,
-            factor:      cursor.parse_with_space()?,
-            correct_mgo: cursor.parse_with_space()?,
-            coefficient: cursor.parse_with_space()?,
-            ty:          cursor.proceed(3).to_string(),
+        parse_line(line, "bond", |cursor| {
+            Ok(Bond {
+                index:       cursor.parse_with_space()?,
+                pair:        cursor.parse_with_space()?,
+                length:      cursor.parse_with_space()?,
+                factor:      cursor.parse_with_space()?,
+                correct_mgo: cursor.parse_with_space()?,
+                coefficient: cursor.parse_with_space()?,
+                ty:          cursor.proceed(3).to_string(),
+            })
         })
     }
 }
@@ -270,15 +353,16 @@ impl FromStr for Angle {
     type Err = error::Error;
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        let mut cursor = LineCursor::new(&line[4..]);
-        Ok(Angle {
-            index:       cursor.parse_with_space()?,
-            triple:      cursor.parse_with_space()?,
-            angle:       cursor.parse_with_space()?,
-            factor:      cursor.parse_with_space()?,
-            correct_mgo: cursor.parse_with_space()?,
-            coefficient: cursor.parse_with_space()?,
-            ty:          cursor.proceed(4).to_string(),
+        parse_line(line, "angl", |cursor| {
+            Ok(Angle {
+                index:       cursor.parse_with_space()?,
+                triple:      cursor.parse_with_space()?,
+                angle:       cursor.parse_with_space()?,
+                factor:      cursor.parse_with_space()?,
+                correct_mgo: cursor.parse_with_space()?,
+                coefficient: cursor.parse_with_space()?,
+                ty:          cursor.proceed(4).to_string(),
+            })
         })
     }
 }
@@ -313,16 +397,17 @@ impl FromStr for DihedralAngle {
     type Err = error::Error;
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        let mut cursor = LineCursor::new(&line[4..]);
-        Ok(DihedralAngle {
-            index:         cursor.parse_with_space()?,
-            quad:          cursor.parse_with_space()?,
-            angle:         cursor.parse_with_space()?,
-            factor:        cursor.parse_with_space()?,
-            correct_mgo:   cursor.parse_with_space()?,
-            coefficient1:  cursor.parse_with_space()?,
-            coefficient3:  cursor.parse_with_space()?,
-            ty:            cursor.proceed(5).to_string(),
+        parse_line(line, "dihd", |cursor| {
+            Ok(DihedralAngle {
+                index:         cursor.parse_with_space()?,
+                quad:          cursor.parse_with_space()?,
+                angle:         cursor.parse_with_space()?,
+                factor:        cursor.parse_with_space()?,
+                correct_mgo:   cursor.parse_with_space()?,
+                coefficient1:  cursor.parse_with_space()?,
+                coefficient3:  cursor.parse_with_space()?,
+                ty:            cursor.proceed(5).to_string(),
+            })
         })
     }
 }
@@ -357,15 +442,16 @@ impl FromStr for Contact {
     type Err = error::Error;
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        let mut cursor = LineCursor::new(&line[7..]);
-        Ok(Contact {
-            index:       cursor.parse_with_space()?,
-            pair:        cursor.parse_with_space()?,
-            length:      cursor.parse()?,
-            factor:      cursor.parse()?,
-            dummy:       cursor.parse_with_space()?,
-            coefficient: cursor.parse()?,
-            ty:          cursor.proceed(4).to_string(),
+        parse_line(line, "contact", |cursor| {
+            Ok(Contact {
+                index:       cursor.parse_with_space()?,
+                pair:        cursor.parse_with_space()?,
+                length:      cursor.parse()?,
+                factor:      cursor.parse()?,
+                dummy:       cursor.parse_with_space()?,
+                coefficient: cursor.parse()?,
+                ty:          cursor.proceed(4).to_string(),
+            })
         })
     }
 }
@@ -375,10 +461,10 @@ impl fmt::Display for Contact {
         write!(f, "contact")?;
         write_with_space(f, self.index)?;
         write_with_space(f, &self.pair)?;
-        write(f, self.length)?;
-        write(f, self.factor)?;
+        write_packed(f, self.length)?;
+        write_packed(f, self.factor)?;
         write_with_space(f, self.dummy)?;
-        write(f, self.coefficient)?;
+        write_packed(f, self.coefficient)?;
         write_with_space(f, &self.ty)?;
         Ok(())
     }
@@ -400,16 +486,17 @@ impl FromStr for AicgAngle {
     type Err = error::Error;
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        let mut cursor = LineCursor::new(&line[6..]);
-        Ok(AicgAngle {
-            index:       cursor.parse_with_space()?,
-            triple:      cursor.parse_with_space()?,
-            value:       cursor.parse_with_space()?,
-            factor:      cursor.parse_with_space()?,
-            correct_mgo: cursor.parse_with_space()?,
-            coefficient: cursor.parse_with_space()?,
-            width:       cursor.parse_with_space()?,
-            ty:          cursor.proceed(4).to_string(),
+        parse_line(line, "aicg13", |cursor| {
+            Ok(AicgAngle {
+                index:       cursor.parse_with_space()?,
+                triple:      cursor.parse_with_space()?,
+                value:       cursor.parse_with_space()?,
+                factor:      cursor.parse_with_space()?,
+                correct_mgo: cursor.parse_with_space()?,
+                coefficient: cursor.parse_with_space()?,
+                width:       cursor.parse_with_space()?,
+                ty:          cursor.proceed(4).to_string(),
+            })
         })
     }
 }
@@ -445,16 +532,17 @@ impl FromStr for AicgDihedralAngle {
     type Err = error::Error;
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        let mut cursor = LineCursor::new(&line[7..]);
-        Ok(AicgDihedralAngle {
-            index:       cursor.parse_with_space()?,
-            quad:        cursor.parse_with_space()?,
-            value:       cursor.parse_with_space()?,
-            factor:      cursor.parse_with_space()?,
-            correct_mgo: cursor.parse_with_space()?,
-            coefficient: cursor.parse_with_space()?,
-            width:       cursor.parse_with_space()?,
-            ty:          cursor.proceed(5).to_string(),
+        parse_line(line, "aicgdih", |cursor| {
+            Ok(AicgDihedralAngle {
+                index:       cursor.parse_with_space()?,
+                quad:        cursor.parse_with_space()?,
+                value:       cursor.parse_with_space()?,
+                factor:      cursor.parse_with_space()?,
+                correct_mgo: cursor.parse_with_space()?,
+                coefficient: cursor.parse_with_space()?,
+                width:       cursor.parse_with_space()?,
+                ty:          cursor.proceed(5).to_string(),
+            })
         })
     }
 }
@@ -645,4 +733,78 @@ mod tests {
 
         assert_eq!(&angle.to_string(), line);
     }
+
+    #[test]
+    fn test_parse_contact_with_overflowed_columns() {
+        let line = "contact 1000000      1      1 999999 1000001 999999 1000001      6.2398      1.0000      1 123456789.1234 p-p";
+        let contact: Contact = line.parse().unwrap();
+
+        assert_eq!(contact.index, 1000000);
+
+        assert_eq!(contact.pair.0.unit, 1);
+        assert_eq!(contact.pair.0.index, 999999);
+        assert_eq!(contact.pair.0.intra_index, 999999);
+
+        assert_eq!(contact.pair.1.unit, 1);
+        assert_eq!(contact.pair.1.index, 1000001);
+        assert_eq!(contact.pair.1.intra_index, 1000001);
+
+        assert_eq!(contact.length, 6.2398);
+        assert_eq!(contact.factor, 1.0);
+        assert_eq!(contact.dummy, 1);
+        assert_eq!(contact.coefficient, 123456789.1234);
+        assert_eq!(contact.ty, "p-p");
+
+        assert_eq!(&contact.to_string(), line);
+    }
+
+    #[test]
+    fn test_write_overflowed_packed_columns() {
+        let line = "contact      1      1      1      2     63      2     63 123456789.0000      1.0000      1      0.5986 p-p";
+        let contact: Contact = line.parse().unwrap();
+        assert_eq!(contact.length, 123456789.0);
+        assert_eq!(&contact.to_string(), line);
+    }
+
+    #[test]
+    fn test_roundtrip_million_particles() {
+        for index in 999_990..1_000_010 {
+            let particle = |index| Particle { unit: 1, index, intra_index: index };
+            let bond = Bond {
+                index,
+                pair:        (particle(index), particle(index + 1)),
+                length:      3.8,
+                factor:      1.0,
+                correct_mgo: 1.0,
+                coefficient: index as f64 * 100.0,
+                ty:          "pp".to_string(),
+            };
+            let parsed: Bond = bond.to_string().parse().unwrap();
+            assert_eq!(parsed.index, index);
+            assert_eq!(parsed.pair.1.index, index + 1);
+            assert_eq!(parsed.pair.1.intra_index, index + 1);
+            assert_eq!(parsed.coefficient, index as f64 * 100.0);
+            assert_eq!(parsed.ty, "pp");
+
+            let contact = Contact {
+                index,
+                pair:        (particle(index), particle(2 * index)),
+                length:      index as f64,
+                factor:      1.0,
+                dummy:       1,
+                coefficient: index as f64 * 10.0,
+                ty:          "p-p".to_string(),
+            };
+            let parsed: Contact = contact.to_string().parse().unwrap();
+            assert_eq!(parsed.index, index);
+            assert_eq!(parsed.pair.0.index, index);
+            assert_eq!(parsed.pair.1.index, 2 * index);
+            assert_eq!(parsed.length, index as f64);
+            assert_eq!(parsed.factor, 1.0);
+            assert_eq!(parsed.dummy, 1);
+            assert_eq!(parsed.coefficient, index as f64 * 10.0);
+            assert_eq!(parsed.ty, "p-p");
+            assert_eq!(parsed.to_string(), contact.to_string());
+        }
+    }
 }
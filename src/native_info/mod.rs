@@ -2,10 +2,11 @@ mod line;
 pub use self::line::*;
 
 use error;
-use block::ReadBlockExt;
+use block::{format_block, ReadBlockExt};
+use geometry::{distance, Vector3d};
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use std::fmt;
-use std::io;
 
 #[derive(Clone)]
 pub struct Particle {
@@ -38,7 +39,7 @@ fn convert_all<T: FromStr>(lines: &Vec<String>) -> Vec<T> where
 }
 
 impl NativeInfo {
-    pub fn load<R: ReadBlockExt>(reader: R) -> io::Result<Self> {
+    pub fn load<R: ReadBlockExt>(reader: R) -> error::Result<Self> {
         let mut bonds = Vec::new();
         let mut angles = Vec::new();
         let mut dihedral_angles = Vec::new();
@@ -47,6 +48,7 @@ impl NativeInfo {
         let mut aicg_dihedral_angles = Vec::new();
 
         for block in reader.blocks() {
+            let block = block?;
             match block.label.as_str() {
                 "native bond length"     => bonds.extend(convert_all(&block.lines)),
                 "native bond angles"     => angles.extend(convert_all(&block.lines)),
@@ -71,22 +73,143 @@ impl NativeInfo {
     }
 }
 
+/// A native contact is formed within this multiple of its native length.
+pub const CONTACT_FACTOR: f64 = 1.2;
+
+impl Contact {
+    /// Returns whether the contact is formed in `positions`, where particle
+    /// `i` is at `positions[i - 1]`. A contact of particles beyond the end
+    /// of `positions` is not formed; see `NativeInfo::check_num_particles`.
+    pub fn is_formed(&self, positions: &[Vector3d]) -> bool {
+        let (ref particle0, ref particle1) = self.pair;
+        let get = |index: usize| index.checked_sub(1).and_then(|i| positions.get(i));
+        match (get(particle0.index), get(particle1.index)) {
+            (Some(x), Some(y)) => distance(x, y) as f64 <= self.length * CONTACT_FACTOR,
+            _ => false,
+        }
+    }
+}
+
+fn fraction_formed<'a, I>(contacts: I, positions: &[Vector3d]) -> f64
+    where I: Iterator<Item = &'a Contact>
+{
+    let (mut formed, mut total) = (0, 0);
+    for contact in contacts {
+        total += 1;
+        if contact.is_formed(positions) {
+            formed += 1;
+        }
+    }
+    if total == 0 {
+        0.0
+    } else {
+        formed as f64 / total as f64
+    }
+}
+
+impl NativeInfo {
+    /// Checks that the contacts are between particles of a structure of
+    /// `num_particles`, such as the frames of a trajectory.
+    pub fn check_num_particles(&self, num_particles: usize) -> error::Result<()> {
+        let largest = self.contacts.iter()
+                                   .map(|contact| contact.pair.0.index.max(contact.pair.1.index))
+                                   .max()
+                                   .unwrap_or(0);
+        if largest > num_particles {
+            return Err(error::Error::ParticleCount { expected: largest, found: num_particles });
+        }
+        Ok(())
+    }
+
+    /// Returns the fraction of native contacts formed in `positions`.
+    pub fn qscore(&self, positions: &[Vector3d]) -> f64 {
+        fraction_formed(self.contacts.iter(), positions)
+    }
+
+    /// Returns the fraction of the native contacts within `unit` formed in
+    /// `positions`.
+    pub fn unit_qscore(&self, positions: &[Vector3d], unit: usize) -> f64 {
+        let contacts = self.contacts.iter().filter(|contact| {
+            contact.pair.0.unit == unit && contact.pair.1.unit == unit
+        });
+        fraction_formed(contacts, positions)
+    }
+
+    /// Returns the 1-based indices of the particles of each unit, as far as
+    /// they appear in the bonds and contacts.
+    pub fn unit_particles(&self) -> BTreeMap<usize, Vec<usize>> {
+        let mut units: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        let pairs = self.bonds.iter().map(|bond| &bond.pair)
+                              .chain(self.contacts.iter().map(|contact| &contact.pair));
+        for (particle0, particle1) in pairs {
+            for particle in &[particle0, particle1] {
+                units.entry(particle.unit).or_default().push(particle.index);
+            }
+        }
+        for particles in units.values_mut() {
+            particles.sort();
+            particles.dedup();
+        }
+        units
+    }
+}
+
 impl fmt::Display for NativeInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if !self.contacts.is_empty() {
-            writeln!(f, "<<<< native contact")?;
-            writeln!(f, "** total_contact =   {}", self.contacts.len())?;
-            writeln!(f, "** definition_of_contact =       6.50 A")?;
-            writeln!(f, "** coef_go(kcal/mol) = factor_go * icon_dummy_mgo * cgo1210 * energy_unit_protein")?;
-            writeln!(f, "")?;
-            writeln!(f, "** contact between unit      1 and      1")?;
-            writeln!(f, "** total_contact_unit =   {}", self.contacts.len())?;
-            writeln!(f, "**        icon iunit1-iunit2   imp1 - imp2 imp1un-imp2un      go_nat   factor_go  dummy     coef_go")?;
-            for contact in &self.contacts {
-                writeln!(f, "{}", contact)?;
-            }
-            writeln!(f, ">>>>")?;
+            let comments = [
+                format!("total_contact =   {}", self.contacts.len()),
+                "definition_of_contact =       6.50 A".to_string(),
+                "coef_go(kcal/mol) = factor_go * icon_dummy_mgo * cgo1210 * energy_unit_protein".to_string(),
+                "".to_string(),
+                "contact between unit      1 and      1".to_string(),
+                format!("total_contact_unit =   {}", self.contacts.len()),
+                "       icon iunit1-iunit2   imp1 - imp2 imp1un-imp2un      go_nat   factor_go  dummy     coef_go".to_string(),
+            ];
+            write!(f, "{}", format_block("native contact", &comments, &self.contacts))?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qscore() {
+        let contacts = [
+            "contact      1      1      1      1      3      1      3      5.0000      1.0000      1      0.5986 p-p",
+            "contact      2      1      1      1      4      1      4      5.0000      1.0000      1      0.5986 p-p",
+            "contact      3      1      2      2      5      1      1      5.0000      1.0000      1      0.5986 p-p",
+        ];
+        let ninfo = NativeInfo {
+            bonds: Vec::new(),
+            angles: Vec::new(),
+            dihedral_angles: Vec::new(),
+            contacts: contacts.iter().map(|line| line.parse().unwrap()).collect(),
+            aicg_angles: Vec::new(),
+            aicg_dihedral_angles: Vec::new(),
+        };
+        let positions = [(0.0, 0.0, 0.0), (3.8, 0.0, 0.0), (5.9, 0.0, 0.0), (6.1, 0.0, 0.0), (9.0, 0.0, 0.0)];
+        assert!(ninfo.contacts[0].is_formed(&positions));
+        assert!(!ninfo.contacts[1].is_formed(&positions));
+        assert!(ninfo.contacts[2].is_formed(&positions));
+        assert_eq!(ninfo.qscore(&positions), 2.0 / 3.0);
+        assert_eq!(ninfo.unit_qscore(&positions, 1), 0.5);
+        assert_eq!(ninfo.unit_qscore(&positions, 3), 0.0);
+
+        assert!(ninfo.check_num_particles(5).is_ok());
+        assert!(ninfo.check_num_particles(4).is_err());
+        assert!(!ninfo.contacts[2].is_formed(&positions[..4]));
+
+        let units = ninfo.unit_particles();
+        assert_eq!(units[&1], vec![1, 2, 3, 4]);
+        assert_eq!(units[&2], vec![5]);
+
+        // The blank line between the comments is kept as CafeMol writes it.
+        let output = ninfo.to_string();
+        assert!(output.starts_with("<<<< native contact\n** total_contact =   3\n"));
+        assert!(output.contains("energy_unit_protein\n\n** contact between unit      1 and      1\n"));
+    }
+}
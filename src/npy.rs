@@ -0,0 +1,287 @@
+//! Writing NumPy `.npy` arrays and `.npz` archives of them.
+//!
+//! Tables are written as arrays of structured dtype with a named field per
+//! column, which NumPy reads with `np.load(filename)["qscore"]`.
+
+use std::io::{self, Write};
+
+/// The type of an element or field, stored little-endian.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Type {
+    Int32,
+    Float32,
+    Float64,
+}
+
+impl Type {
+    fn descr(&self) -> &'static str {
+        match *self {
+            Type::Int32 => "<i4",
+            Type::Float32 => "<f4",
+            Type::Float64 => "<f8",
+        }
+    }
+
+    fn push(&self, data: &mut Vec<u8>, value: f64) {
+        match *self {
+            Type::Int32 => data.extend_from_slice(&(value as i32).to_le_bytes()),
+            Type::Float32 => data.extend_from_slice(&(value as f32).to_le_bytes()),
+            Type::Float64 => data.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+}
+
+/// An array with its dtype, shape and little-endian data in C order.
+#[derive(Clone, Debug)]
+pub struct Array {
+    descr: String,
+    shape: Vec<usize>,
+    data: Vec<u8>,
+}
+
+impl Array {
+    /// Returns an array of `shape` filled row by row with `values`.
+    ///
+    /// Panics if the number of values does not match the shape.
+    pub fn new(element: Type, shape: Vec<usize>, values: &[f64]) -> Self {
+        assert_eq!(values.len(), shape.iter().product::<usize>(),
+                   "{} values do not fill an array of shape {:?}", values.len(), shape);
+        let mut data = Vec::new();
+        for &value in values {
+            element.push(&mut data, value);
+        }
+        Array { descr: format!("'{}'", element.descr()), shape, data }
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let shape = match self.shape.len() {
+            1 => format!("({},)", self.shape[0]),
+            _ => {
+                let lengths: Vec<_> = self.shape.iter().map(|length| length.to_string()).collect();
+                format!("({})", lengths.join(", "))
+            }
+        };
+        let mut dict = format!("{{'descr': {}, 'fortran_order': False, 'shape': {}, }}", self.descr, shape);
+
+        // The magic, the version and the header length precede the header,
+        // which is padded with spaces to align the data to 64 bytes.
+        let (version, prefix) = if dict.len() + 64 <= 0xffff { (1, 10) } else { (2, 12) };
+        let padding = (64 - (prefix + dict.len() + 1) % 64) % 64;
+        dict.push_str(&" ".repeat(padding));
+        dict.push('\n');
+
+        let mut header = b"\x93NUMPY".to_vec();
+        header.extend_from_slice(&[version, 0]);
+        if version == 1 {
+            header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+        } else {
+            header.extend_from_slice(&(dict.len() as u32).to_le_bytes());
+        }
+        header.extend_from_slice(dict.as_bytes());
+        header
+    }
+
+    /// Writes the array in the `.npy` format.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.header())?;
+        writer.write_all(&self.data)
+    }
+
+    fn to_npy(&self) -> Vec<u8> {
+        let mut bytes = self.header();
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+}
+
+/// A table of rows with named fields of possibly different types.
+#[derive(Clone, Debug)]
+pub struct Table {
+    fields: Vec<(String, Type)>,
+    data: Vec<u8>,
+    num_rows: usize,
+}
+
+impl Table {
+    pub fn new(fields: Vec<(String, Type)>) -> Self {
+        Table { fields, data: Vec::new(), num_rows: 0 }
+    }
+
+    /// Appends a row with a value per field, each converted to the type of
+    /// its field. Missing values at the end are written as NaN, or as zero
+    /// in integer fields.
+    pub fn push(&mut self, row: &[f64]) {
+        for (i, &(_, element)) in self.fields.iter().enumerate() {
+            element.push(&mut self.data, row.get(i).cloned().unwrap_or(f64::NAN));
+        }
+        self.num_rows += 1;
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    /// Returns the table as a 1-dimensional array of structured dtype.
+    pub fn into_array(self) -> Array {
+        let fields: Vec<_> = self.fields.iter()
+                                        .map(|&(ref name, element)| format!("('{}', '{}')", name, element.descr()))
+                                        .collect();
+        Array {
+            descr: format!("[{}]", fields.join(", ")),
+            shape: vec![self.num_rows],
+            data: self.data,
+        }
+    }
+}
+
+/// Returns the CRC-32 checksum of a ZIP entry.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Writes `arrays` to an uncompressed `.npz` archive, each as `NAME.npy`.
+///
+/// The archive cannot exceed the 4 GiB of a ZIP file without extensions.
+pub fn write_npz<W: Write>(writer: &mut W, arrays: &[(&str, &Array)]) -> io::Result<()> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "archive exceeds 4 GiB");
+    // The DOS date of the entries, 1980-01-01 at midnight.
+    let (time, date) = (0u16, 0x21u16);
+
+    let mut offset = 0u64;
+    let mut directory = Vec::new();
+    for &(name, array) in arrays {
+        let name = format!("{}.npy", name);
+        let data = array.to_npy();
+        let (crc, size) = (crc32(&data), data.len() as u32);
+        if data.len() as u64 > u32::MAX as u64 || offset > u32::MAX as u64 {
+            return Err(too_large());
+        }
+
+        let mut entry = Vec::new();
+        entry.extend_from_slice(&[20, 0, 0, 0, 0, 0]);
+        entry.extend_from_slice(&time.to_le_bytes());
+        entry.extend_from_slice(&date.to_le_bytes());
+        entry.extend_from_slice(&crc.to_le_bytes());
+        entry.extend_from_slice(&size.to_le_bytes());
+        entry.extend_from_slice(&size.to_le_bytes());
+        entry.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        entry.extend_from_slice(&0u16.to_le_bytes());
+
+        writer.write_all(&0x0403_4b50u32.to_le_bytes())?;
+        writer.write_all(&entry)?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(&data)?;
+
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&entry);
+        // No comment, on disk 0, no attributes, at `offset`.
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&(offset as u32).to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+
+        offset += 30 + name.len() as u64 + data.len() as u64;
+    }
+    if offset > u32::MAX as u64 {
+        return Err(too_large());
+    }
+    writer.write_all(&directory)?;
+
+    let mut end = 0x0605_4b50u32.to_le_bytes().to_vec();
+    end.extend_from_slice(&[0; 4]);
+    end.extend_from_slice(&(arrays.len() as u16).to_le_bytes());
+    end.extend_from_slice(&(arrays.len() as u16).to_le_bytes());
+    end.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    end.extend_from_slice(&(offset as u32).to_le_bytes());
+    end.extend_from_slice(&[0; 2]);
+    writer.write_all(&end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], i: usize) -> u32 {
+        u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_write_array() {
+        let array = Array::new(Type::Float32, vec![2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let mut bytes = Vec::new();
+        array.write(&mut bytes).unwrap();
+
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + length) % 64, 0);
+        let header = String::from_utf8(bytes[10..10 + length].to_vec()).unwrap();
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with(" \n"));
+        assert_eq!(bytes.len(), 10 + length + 24);
+        assert_eq!(&bytes[10 + length..10 + length + 4], &1f32.to_le_bytes());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_array_of_wrong_length() {
+        Array::new(Type::Float32, vec![2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_write_table() {
+        let mut table = Table::new(vec![("step".to_string(), Type::Int32),
+                                        ("qscore".to_string(), Type::Float32)]);
+        table.push(&[1000.0, 0.5]);
+        table.push(&[2000.0]);
+        assert_eq!(table.num_rows(), 2);
+
+        let mut bytes = Vec::new();
+        table.into_array().write(&mut bytes).unwrap();
+        let header = String::from_utf8_lossy(&bytes[10..]).into_owned();
+        assert!(header.starts_with("{'descr': [('step', '<i4'), ('qscore', '<f4')], \
+                                    'fortran_order': False, 'shape': (2,), }"));
+        let data = &bytes[bytes.len() - 16..];
+        assert_eq!(&data[..4], &1000i32.to_le_bytes());
+        assert_eq!(&data[4..8], &0.5f32.to_le_bytes());
+        assert!(f32::from_le_bytes([data[12], data[13], data[14], data[15]]).is_nan());
+    }
+
+    #[test]
+    fn test_write_npz() {
+        let a = Array::new(Type::Int32, vec![3], &[1.0, 2.0, 3.0]);
+        let b = Array::new(Type::Float64, vec![1, 1], &[0.5]);
+        let mut bytes = Vec::new();
+        write_npz(&mut bytes, &[("a", &a), ("b", &b)]).unwrap();
+
+        assert_eq!(u32_at(&bytes, 0), 0x0403_4b50);
+        let end = bytes.len() - 22;
+        assert_eq!(u32_at(&bytes, end), 0x0605_4b50);
+        assert_eq!(u16::from_le_bytes([bytes[end + 10], bytes[end + 11]]), 2);
+        let directory = u32_at(&bytes, end + 16) as usize;
+        assert_eq!(directory + u32_at(&bytes, end + 12) as usize, end);
+        assert_eq!(u32_at(&bytes, directory), 0x0201_4b50);
+        assert_eq!(&bytes[directory + 46..directory + 51], b"a.npy");
+
+        // The checksum of the first entry covers its data.
+        let size = u32_at(&bytes, 18) as usize;
+        assert_eq!(u32_at(&bytes, 14), crc32(&bytes[35..35 + size]));
+        assert_eq!(&bytes[35..41], b"\x93NUMPY");
+    }
+}
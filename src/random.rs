@@ -0,0 +1,31 @@
+//! A reproducible pseudo-random number generator, for resampling and for the
+//! samples of tests.
+
+/// A 64-bit linear congruential generator with the constants of Knuth's
+/// MMIX, seeded with its initial state.
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0
+    }
+
+    /// Returns an integer in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next() >> 33) as usize) % n
+    }
+
+    /// Returns a number uniformly distributed in `(0, 1)`.
+    #[cfg(test)]
+    pub fn uniform(&mut self) -> f64 {
+        ((self.next() >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    /// Returns a standard normal number by the Box–Muller transform.
+    #[cfg(test)]
+    pub fn normal(&mut self) -> f64 {
+        let (u, v) = (self.uniform(), self.uniform());
+        (-2.0 * u.ln()).sqrt() * (2.0 * ::std::f64::consts::PI * v).cos()
+    }
+}
@@ -0,0 +1,259 @@
+//! Folding rates from the first-passage times of independent runs, some of
+//! which may end before they fold.
+
+use std::f64;
+use random::Lcg;
+
+/// The time a run took to first reach a threshold, or the length of a run
+/// that never reached it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Passage {
+    pub time: f64,
+    pub censored: bool,
+}
+
+impl Passage {
+    /// Returns the time from the first step until `values` first reaches
+    /// `threshold`, or `None` for an empty run.
+    pub fn first(steps: &[i32], values: &[f64], threshold: f64) -> Option<Self> {
+        let start = *steps.first()?;
+        let last = *steps.last()?;
+        match steps.iter().zip(values).find(|&(_, &value)| value >= threshold) {
+            Some((&step, _)) => Some(Passage { time: (step - start) as f64, censored: false }),
+            None => Some(Passage { time: (last - start) as f64, censored: true }),
+        }
+    }
+}
+
+/// Returns the Kaplan-Meier estimate of the probability of not having
+/// folded by each time at which a run folded.
+pub fn survival(passages: &[Passage]) -> Vec<(f64, f64)> {
+    let mut sorted = passages.to_vec();
+    sorted.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(::std::cmp::Ordering::Equal));
+
+    let mut curve = Vec::new();
+    let mut probability = 1.0;
+    let mut i = 0;
+    while i < sorted.len() {
+        let time = sorted[i].time;
+        let at_risk = sorted.len() - i;
+        let same: Vec<_> = sorted[i..].iter().take_while(|passage| passage.time == time).collect();
+        let events = same.iter().filter(|passage| !passage.censored).count();
+        if events > 0 {
+            probability *= 1.0 - events as f64 / at_risk as f64;
+            curve.push((time, probability));
+        }
+        i += same.len();
+    }
+    curve
+}
+
+/// A single exponential survival `exp(-k t)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SingleExponential {
+    pub rate: f64,
+}
+
+impl SingleExponential {
+    /// Returns the maximum-likelihood fit, with censored runs contributing
+    /// their survival, or `None` without any folding event.
+    pub fn fit(passages: &[Passage]) -> Option<Self> {
+        let events = passages.iter().filter(|passage| !passage.censored).count();
+        let total: f64 = passages.iter().map(|passage| passage.time).sum();
+        if events == 0 || total <= 0.0 {
+            return None;
+        }
+        Some(SingleExponential { rate: events as f64 / total })
+    }
+
+    pub fn survival(&self, time: f64) -> f64 {
+        (-self.rate * time).exp()
+    }
+}
+
+/// A double exponential survival `a exp(-k1 t) + (1 - a) exp(-k2 t)` with
+/// `k1 >= k2`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DoubleExponential {
+    pub amplitude: f64,
+    pub fast_rate: f64,
+    pub slow_rate: f64,
+}
+
+/// The maximum number of expectation-maximization iterations of a double
+/// exponential fit and the relative change at which they stop.
+const MAX_ITERATIONS: usize = 10_000;
+const TOLERANCE: f64 = 1e-10;
+
+impl DoubleExponential {
+    /// Returns the maximum-likelihood fit by expectation maximization, or
+    /// `None` with fewer than two folding events.
+    pub fn fit(passages: &[Passage]) -> Option<Self> {
+        let single = SingleExponential::fit(passages)?;
+        if passages.iter().filter(|passage| !passage.censored).count() < 2 {
+            return None;
+        }
+
+        let mut fit = DoubleExponential {
+            amplitude: 0.5,
+            fast_rate: 2.0 * single.rate,
+            slow_rate: 0.5 * single.rate,
+        };
+        for _ in 0..MAX_ITERATIONS {
+            let mut weight = 0.0;
+            let (mut fast_events, mut fast_time) = (0.0, 0.0);
+            let (mut slow_events, mut slow_time) = (0.0, 0.0);
+            for passage in passages {
+                let fast = fit.amplitude * (-fit.fast_rate * passage.time).exp();
+                let slow = (1.0 - fit.amplitude) * (-fit.slow_rate * passage.time).exp();
+                // The probability that the run belongs to the fast phase.
+                let r = if passage.censored {
+                    fast / (fast + slow)
+                } else {
+                    fast * fit.fast_rate / (fast * fit.fast_rate + slow * fit.slow_rate)
+                };
+                let r = if r.is_finite() { r } else { 0.0 };
+                weight += r;
+                fast_time += r * passage.time;
+                slow_time += (1.0 - r) * passage.time;
+                if !passage.censored {
+                    fast_events += r;
+                    slow_events += 1.0 - r;
+                }
+            }
+
+            let next = DoubleExponential {
+                amplitude: weight / passages.len() as f64,
+                fast_rate: if fast_time > 0.0 { fast_events / fast_time } else { fit.fast_rate },
+                slow_rate: if slow_time > 0.0 { slow_events / slow_time } else { fit.slow_rate },
+            };
+            let converged = (next.amplitude - fit.amplitude).abs() < TOLERANCE
+                && (next.fast_rate - fit.fast_rate).abs() < TOLERANCE * fit.fast_rate
+                && (next.slow_rate - fit.slow_rate).abs() < TOLERANCE * fit.slow_rate.max(f64::MIN_POSITIVE);
+            fit = next;
+            if converged {
+                break;
+            }
+        }
+
+        if fit.fast_rate < fit.slow_rate {
+            fit = DoubleExponential {
+                amplitude: 1.0 - fit.amplitude,
+                fast_rate: fit.slow_rate,
+                slow_rate: fit.fast_rate,
+            };
+        }
+        Some(fit)
+    }
+
+    pub fn survival(&self, time: f64) -> f64 {
+        self.amplitude * (-self.fast_rate * time).exp()
+            + (1.0 - self.amplitude) * (-self.slow_rate * time).exp()
+    }
+}
+
+/// Returns `num_samples` fits of `fit` to the runs resampled with
+/// replacement, leaving out the resamples that cannot be fitted.
+pub fn bootstrap<T, F>(passages: &[Passage], num_samples: usize, seed: u64, fit: F) -> Vec<T>
+    where F: Fn(&[Passage]) -> Option<T>
+{
+    if passages.is_empty() {
+        return Vec::new();
+    }
+    let mut rng = Lcg(seed);
+    (0..num_samples).filter_map(|_| {
+        let resample: Vec<_> = (0..passages.len()).map(|_| passages[rng.below(passages.len())]).collect();
+        fit(&resample)
+    }).collect()
+}
+
+/// Returns the `(1 - level) / 2` and `(1 + level) / 2` quantiles of
+/// `samples`, or `None` if there are none.
+pub fn confidence_interval(samples: &[f64], level: f64) -> Option<(f64, f64)> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(::std::cmp::Ordering::Equal));
+    let quantile = |q: f64| sorted[((q * (sorted.len() - 1) as f64).round() as usize).min(sorted.len() - 1)];
+    Some((quantile((1.0 - level) / 2.0), quantile((1.0 + level) / 2.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exponentially distributed times with `rate`, from a stratified grid
+    /// of quantiles so that the sample is reproducible.
+    fn exponential(rate: f64, n: usize) -> Vec<f64> {
+        (0..n).map(|i| -((i as f64 + 0.5) / n as f64).ln() / rate).collect()
+    }
+
+    fn censor(times: &[f64], limit: f64) -> Vec<Passage> {
+        times.iter().map(|&time| {
+            if time > limit {
+                Passage { time: limit, censored: true }
+            } else {
+                Passage { time, censored: false }
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn test_first_passage() {
+        let steps = [100, 200, 300, 400];
+        let passage = Passage::first(&steps, &[0.1, 0.5, 0.8, 0.9], 0.7).unwrap();
+        assert_eq!(passage, Passage { time: 200.0, censored: false });
+        let passage = Passage::first(&steps, &[0.1, 0.5, 0.6, 0.5], 0.7).unwrap();
+        assert_eq!(passage, Passage { time: 300.0, censored: true });
+        assert!(Passage::first(&[], &[], 0.7).is_none());
+    }
+
+    #[test]
+    fn test_survival() {
+        let passages = [
+            Passage { time: 1.0, censored: false },
+            Passage { time: 2.0, censored: true },
+            Passage { time: 3.0, censored: false },
+            Passage { time: 3.0, censored: false },
+            Passage { time: 4.0, censored: true },
+        ];
+        assert_eq!(survival(&passages), vec![(1.0, 0.8), (3.0, 0.8 * (1.0 - 2.0 / 3.0))]);
+    }
+
+    #[test]
+    fn test_single_exponential() {
+        let rate = 1e-4;
+        let passages = censor(&exponential(rate, 2000), 20000.0);
+        assert!(passages.iter().any(|passage| passage.censored));
+        let fit = SingleExponential::fit(&passages).unwrap();
+        assert!((fit.rate - rate).abs() < 0.02 * rate, "k = {}", fit.rate);
+        assert!(SingleExponential::fit(&censor(&[5.0], 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_double_exponential() {
+        let mut times = exponential(1e-2, 600);
+        times.extend(exponential(1e-4, 1400));
+        let passages = censor(&times, 30000.0);
+        let fit = DoubleExponential::fit(&passages).unwrap();
+        assert!((fit.amplitude - 0.3).abs() < 0.03, "a = {}", fit.amplitude);
+        assert!((fit.fast_rate - 1e-2).abs() < 0.15e-2, "k1 = {}", fit.fast_rate);
+        assert!((fit.slow_rate - 1e-4).abs() < 0.1e-4, "k2 = {}", fit.slow_rate);
+    }
+
+    #[test]
+    fn test_bootstrap() {
+        let rate = 1e-3;
+        let passages = censor(&exponential(rate, 500), 3000.0);
+        let rates: Vec<_> = bootstrap(&passages, 500, 1, SingleExponential::fit)
+            .iter()
+            .map(|fit| fit.rate)
+            .collect();
+        assert_eq!(rates.len(), 500);
+        let (lower, upper) = confidence_interval(&rates, 0.95).unwrap();
+        assert!(lower < rate && rate < upper, "{} < {} < {}", lower, rate, upper);
+        assert!(upper - lower < 0.5 * rate);
+        assert_eq!(confidence_interval(&[3.0, 1.0, 2.0], 0.0), Some((2.0, 2.0)));
+    }
+}
@@ -0,0 +1,378 @@
+//! Replica-exchange histories and demultiplexing of per-replica output.
+//!
+//! A history file lists, after `#` comment lines, a row for each exchange:
+//! the step followed by the 1-based label (temperature index) of each
+//! replica from that step until the next row.
+//!
+//! ```text
+//! #  step  replica 1 2 3 4
+//!       0  1 2 3 4
+//!    1000  2 1 3 4
+//! ```
+
+use std::io::prelude::*;
+use error::{Error, Result};
+use time_series::{TimeSeries, TimeStep};
+
+/// The labels held by the replicas from `step` on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exchange {
+    pub step: i32,
+    /// The 0-based label of each replica.
+    pub labels: Vec<usize>,
+}
+
+impl Exchange {
+    /// Returns the replica holding `label`.
+    pub fn replica_of(&self, label: usize) -> Option<usize> {
+        self.labels.iter().position(|&l| l == label)
+    }
+
+    /// Returns the replica whose output belongs to label `target`, or with
+    /// `by_replica` the label whose output belongs to replica `target`.
+    pub fn source(&self, target: usize, by_replica: bool) -> Option<usize> {
+        if by_replica {
+            self.labels.get(target).cloned()
+        } else {
+            self.replica_of(target)
+        }
+    }
+}
+
+/// The number of exchanges attempted and accepted between two neighbouring
+/// labels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Acceptance {
+    pub attempted: usize,
+    pub accepted: usize,
+}
+
+impl Acceptance {
+    pub fn ratio(&self) -> f64 {
+        if self.attempted == 0 {
+            0.0
+        } else {
+            self.accepted as f64 / self.attempted as f64
+        }
+    }
+}
+
+/// A frame of trajectories sorted by label or by replica; see
+/// `History::align_frames`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlignedFrame {
+    /// The index of the frame in every trajectory.
+    pub index: usize,
+    pub step: i32,
+    /// The trajectory whose frame belongs to each label, or replica.
+    pub sources: Vec<usize>,
+}
+
+/// The exchange history of a replica-exchange run.
+#[derive(Clone, Debug)]
+pub struct History {
+    pub exchanges: Vec<Exchange>,
+}
+
+fn parse_exchange(line: &str) -> Result<Exchange> {
+    let mut fields = line.split_whitespace();
+    let step = match fields.next() {
+        Some(step) => step.parse()?,
+        None => return Err(Error::InvalidLine(line.to_string())),
+    };
+    let mut labels = Vec::new();
+    for field in fields {
+        let label: usize = field.parse()?;
+        if label == 0 {
+            return Err(Error::InvalidLine(line.to_string()));
+        }
+        labels.push(label - 1);
+    }
+
+    // The labels must be a permutation of 1..N.
+    let mut sorted = labels.clone();
+    sorted.sort();
+    if sorted.is_empty() || sorted.iter().enumerate().any(|(i, &label)| i != label) {
+        return Err(Error::InvalidLine(line.to_string()));
+    }
+    Ok(Exchange { step, labels })
+}
+
+impl History {
+    pub fn load<R: BufRead>(reader: R) -> Result<Self> {
+        let mut exchanges: Vec<Exchange> = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let exchange = parse_exchange(&line).map_err(|err| err.at_line(i + 1))?;
+            if let Some(last) = exchanges.last() {
+                if exchange.labels.len() != last.labels.len() {
+                    let error = Error::ColumnCount {
+                        expected: last.labels.len() + 1,
+                        found: exchange.labels.len() + 1,
+                    };
+                    return Err(error.at_line(i + 1));
+                }
+                if exchange.step <= last.step {
+                    let error = Error::NonMonotonicSteps { previous: last.step, step: exchange.step };
+                    return Err(error.at_line(i + 1));
+                }
+            }
+            exchanges.push(exchange);
+        }
+        Ok(History { exchanges })
+    }
+
+    pub fn num_replicas(&self) -> usize {
+        self.exchanges.first().map_or(0, |exchange| exchange.labels.len())
+    }
+
+    /// Returns the exchange in effect at `step`, or `None` before the first.
+    pub fn at(&self, step: i32) -> Option<&Exchange> {
+        match self.exchanges.binary_search_by_key(&step, |exchange| exchange.step) {
+            Ok(i) => Some(&self.exchanges[i]),
+            Err(0) => None,
+            Err(i) => Some(&self.exchanges[i - 1]),
+        }
+    }
+
+    /// Returns the acceptance of exchanges between labels `i` and `i + 1`.
+    ///
+    /// Exchanges alternate between the pairs starting at even and at odd
+    /// labels, so each step between two rows attempts one of the two sets.
+    /// The set is recognized by the pairs that swapped, and steps without
+    /// any swap are assumed to continue the alternation.
+    pub fn acceptance(&self) -> Vec<Acceptance> {
+        let num_pairs = self.num_replicas().saturating_sub(1);
+        let mut acceptance = vec![Acceptance::default(); num_pairs];
+
+        let swaps: Vec<Vec<usize>> = self.exchanges.windows(2).map(|pair| {
+            (0..num_pairs).filter(|&label| {
+                match (pair[0].replica_of(label), pair[1].replica_of(label + 1)) {
+                    (Some(a), Some(b)) => a == b && pair[0].replica_of(label + 1) == pair[1].replica_of(label),
+                    _ => false,
+                }
+            }).collect()
+        }).collect();
+
+        // The parity of the first step, from the first step with a swap.
+        let first_parity = swaps.iter()
+                                .enumerate()
+                                .find(|&(_, pairs)| !pairs.is_empty())
+                                .map_or(0, |(i, pairs)| (pairs[0] + i) % 2);
+
+        for (i, pairs) in swaps.iter().enumerate() {
+            let parity = (first_parity + i) % 2;
+            for (label, entry) in acceptance.iter_mut().enumerate() {
+                if label % 2 == parity {
+                    entry.attempted += 1;
+                }
+            }
+            for &label in pairs {
+                acceptance[label].accepted += 1;
+            }
+        }
+        acceptance
+    }
+
+    /// Returns the durations in steps of the round trips of each replica
+    /// from the lowest label to the highest and back.
+    pub fn round_trips(&self) -> Vec<Vec<i32>> {
+        let num_replicas = self.num_replicas();
+        let top = num_replicas.saturating_sub(1);
+        (0..num_replicas).map(|replica| {
+            let mut trips = Vec::new();
+            let mut start = None;
+            let mut reached_top = false;
+            for exchange in &self.exchanges {
+                let label = exchange.labels[replica];
+                if label == 0 {
+                    if let (Some(start), true) = (start, reached_top) {
+                        trips.push(exchange.step - start);
+                    }
+                    if start.is_none() || reached_top {
+                        start = Some(exchange.step);
+                        reached_top = false;
+                    }
+                } else if label == top && start.is_some() {
+                    reached_top = true;
+                }
+            }
+            trips
+        }).collect()
+    }
+
+    /// Sorts per-replica time series into one for each label, or the
+    /// inverse with `by_replica`. The steps missing from a source or before
+    /// the first exchange are left out.
+    pub fn demultiplex(&self, series: &[TimeSeries], by_replica: bool) -> Vec<TimeSeries> {
+        let mut sorted: Vec<_> = series.iter().map(|ts| TimeSeries {
+            header: ts.header.clone(),
+            columns: ts.columns.clone(),
+            steps: Vec::new(),
+        }).collect();
+        let first = match series.first() {
+            Some(first) => first,
+            None => return sorted,
+        };
+
+        for (i, time_step) in first.steps.iter().enumerate() {
+            let exchange = match self.at(time_step.step) {
+                Some(exchange) => exchange,
+                None => continue,
+            };
+            for (target, ts) in sorted.iter_mut().enumerate() {
+                let source = exchange.source(target, by_replica);
+                if let Some(found) = source.and_then(|source| step_of(series.get(source)?, i, time_step.step)) {
+                    ts.steps.push(found.clone());
+                }
+            }
+        }
+        sorted
+    }
+
+    /// Aligns the frames of per-replica trajectories, where `steps` holds
+    /// the step of each frame of each replica, and returns the frames to
+    /// write for each label, or for each replica with `by_replica`. The
+    /// trajectories must be written in step with each other; the frames
+    /// before the first exchange or beyond the shortest trajectory are left
+    /// out.
+    pub fn align_frames(&self, steps: &[Vec<i32>], by_replica: bool) -> Result<Vec<AlignedFrame>> {
+        if steps.len() != self.num_replicas() {
+            return Err(Error::FileCount { expected: self.num_replicas(), found: steps.len() });
+        }
+        let num_frames = steps.iter().map(Vec::len).min().unwrap_or(0);
+        let mut frames = Vec::new();
+        for index in 0..num_frames {
+            let step = steps[0][index];
+            if let Some(other) = steps.iter().find(|steps| steps[index] != step) {
+                return Err(Error::StepMismatch { frame: index, expected: step, found: other[index] });
+            }
+            if let Some(exchange) = self.at(step) {
+                let sources = (0..steps.len()).map(|target| {
+                    exchange.source(target, by_replica).expect("labels are a permutation")
+                }).collect();
+                frames.push(AlignedFrame { index, step, sources });
+            }
+        }
+        Ok(frames)
+    }
+}
+
+/// Returns the `step` of `ts`, expected at `index` as in the other files.
+fn step_of(ts: &TimeSeries, index: usize, step: i32) -> Option<&TimeStep> {
+    match ts.steps.get(index) {
+        Some(time_step) if time_step.step == step => Some(time_step),
+        _ => ts.get(step),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = "\
+#  step  replica 1 2 3
+      0  1 2 3
+   1000  2 1 3
+   2000  3 1 2
+   3000  3 2 1
+   4000  3 2 1
+   5000  3 1 2
+   6000  2 1 3
+   7000  1 2 3
+";
+
+    #[test]
+    fn test_load_history() {
+        let history = History::load(HISTORY.as_bytes()).unwrap();
+        assert_eq!(history.num_replicas(), 3);
+        assert_eq!(history.exchanges[1], Exchange { step: 1000, labels: vec![1, 0, 2] });
+        assert_eq!(history.at(1500).unwrap().step, 1000);
+        assert_eq!(history.at(2000).unwrap().step, 2000);
+        assert!(history.at(-1).is_none());
+        assert_eq!(history.exchanges[2].replica_of(0), Some(1));
+        assert_eq!(history.exchanges[2].source(0, false), Some(1));
+        assert_eq!(history.exchanges[2].source(0, true), Some(2));
+        assert_eq!(history.exchanges[2].source(3, true), None);
+    }
+
+    #[test]
+    fn test_invalid_history() {
+        let err = History::load("0 1 2\n1000 1 1\n".as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid line '1000 1 1'");
+        assert!(History::load("0 1 2\n1000 1 2 3\n".as_bytes()).is_err());
+        assert!(History::load("0 1 2\n0 2 1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_acceptance() {
+        let history = History::load(HISTORY.as_bytes()).unwrap();
+        // The steps attempt the pairs of labels (1, 2) and (2, 3) in turn,
+        // and only the exchange at step 4000 is rejected.
+        let acceptance = history.acceptance();
+        assert_eq!(acceptance, vec![Acceptance { attempted: 4, accepted: 4 },
+                                    Acceptance { attempted: 3, accepted: 2 }]);
+        assert_eq!(acceptance[1].ratio(), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let history = History::load(HISTORY.as_bytes()).unwrap();
+        // Replica 1 goes from label 1 at step 0 to 3 and back at step 7000,
+        // while replica 3 reaches label 3 again but not 1.
+        let trips = history.round_trips();
+        assert_eq!(trips, vec![vec![7000], vec![], vec![]]);
+    }
+
+    #[test]
+    fn test_align_frames() {
+        let history = History::load("1000 1 2 3\n2000 2 3 1\n".as_bytes()).unwrap();
+        let steps = vec![vec![0, 1000, 2000, 3000], vec![0, 1000, 2000], vec![0, 1000, 2000]];
+
+        // The frame before the first exchange and the fourth frame, which is
+        // not in every trajectory, are left out.
+        let frames = history.align_frames(&steps, false).unwrap();
+        assert_eq!(frames, vec![AlignedFrame { index: 1, step: 1000, sources: vec![0, 1, 2] },
+                                AlignedFrame { index: 2, step: 2000, sources: vec![2, 0, 1] }]);
+        let frames = history.align_frames(&steps, true).unwrap();
+        assert_eq!(frames[1].sources, vec![1, 2, 0]);
+
+        let shifted = vec![vec![0, 1000], vec![0, 1000], vec![0, 1500]];
+        assert_eq!(history.align_frames(&shifted, false).unwrap_err().to_string(),
+                   "frame 1 is at step 1500, not 1000");
+        assert!(history.align_frames(&steps[..2], false).is_err());
+    }
+
+    const REPLICA: &str = "\
+#unit       step    tempk     radg       etot      velet qscore     rmsd
+               0   300.00    25.31     -83.12     123.45  0.986     0.00
+            1000   300.00    25.31     -83.12     123.45  0.986     0.00
+            2000   300.00    25.31     -83.12     123.45  0.986     0.00
+";
+
+    #[test]
+    fn test_demultiplex() {
+        let history = History::load(HISTORY.as_bytes()).unwrap();
+        let series: Vec<_> = (0..3).map(|replica| {
+            let text = REPLICA.replace("0.986", &format!("0.{:03}", replica));
+            TimeSeries::load(text.as_bytes()).unwrap()
+        }).collect();
+
+        let qscore = |ts: &TimeSeries| -> Vec<f32> {
+            ts.series("qscore", None).unwrap().1.iter().map(|&x| x as f32).collect()
+        };
+        let by_label = history.demultiplex(&series, false);
+        assert_eq!(qscore(&by_label[0]), vec![0.0, 0.001, 0.001]);
+        assert_eq!(qscore(&by_label[1]), vec![0.001, 0.0, 0.002]);
+        assert_eq!(qscore(&by_label[2]), vec![0.002, 0.002, 0.0]);
+
+        let by_replica = history.demultiplex(&by_label, true);
+        for (replica, ts) in by_replica.iter().enumerate() {
+            assert_eq!(ts.steps.len(), 3);
+            assert_eq!(qscore(ts), qscore(&series[replica]));
+        }
+    }
+}
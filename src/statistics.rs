@@ -0,0 +1,207 @@
+//! Statistics of correlated samples, such as the columns of a time series.
+
+/// A summary of a series of samples.
+#[derive(Clone, Debug)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    /// The unbiased sample variance.
+    pub variance: f64,
+    pub min: f64,
+    pub max: f64,
+    /// The standard error of the mean estimated by block averaging.
+    pub block_error: f64,
+    /// The integrated autocorrelation time in units of samples.
+    pub autocorrelation_time: f64,
+}
+
+impl Summary {
+    /// Summarizes `samples`, or returns `None` if there are none.
+    pub fn new(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        Some(Summary {
+            count: samples.len(),
+            mean: mean(samples),
+            variance: variance(samples),
+            min: samples.iter().cloned().fold(f64::INFINITY, f64::min),
+            max: samples.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            block_error: block_error(samples),
+            autocorrelation_time: autocorrelation_time(samples),
+        })
+    }
+
+    /// Returns the number of effectively independent samples.
+    pub fn effective_count(&self) -> f64 {
+        self.count as f64 / (2.0 * self.autocorrelation_time).max(1.0)
+    }
+}
+
+pub fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// Returns the unbiased sample variance, or zero for a single sample.
+pub fn variance(samples: &[f64]) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(samples);
+    samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (samples.len() - 1) as f64
+}
+
+/// The minimum number of blocks for a blocking level to be trusted.
+const MIN_BLOCKS: usize = 16;
+
+/// Estimates the standard error of the mean of correlated samples by
+/// repeatedly averaging pairs of neighbouring blocks (Flyvbjerg & Petersen,
+/// 1989), taking the largest estimate among the levels with enough blocks.
+pub fn block_error(samples: &[f64]) -> f64 {
+    let mut blocks = samples.to_vec();
+    let mut error = (variance(&blocks) / blocks.len() as f64).sqrt();
+    while blocks.len() / 2 >= MIN_BLOCKS {
+        blocks = blocks.chunks(2)
+                       .filter(|pair| pair.len() == 2)
+                       .map(|pair| 0.5 * (pair[0] + pair[1]))
+                       .collect();
+        error = error.max((variance(&blocks) / blocks.len() as f64).sqrt());
+    }
+    error
+}
+
+/// Returns the normalized autocorrelation function up to `max_lag`.
+pub fn autocorrelation(samples: &[f64], max_lag: usize) -> Vec<f64> {
+    let n = samples.len();
+    let mean = mean(samples);
+    let deviations: Vec<_> = samples.iter().map(|x| x - mean).collect();
+    let c0 = deviations.iter().map(|d| d * d).sum::<f64>() / n as f64;
+    (0..max_lag.min(n.saturating_sub(1)) + 1).map(|lag| {
+        if c0 == 0.0 {
+            return if lag == 0 { 1.0 } else { 0.0 };
+        }
+        let c = deviations[..n - lag].iter()
+                                     .zip(&deviations[lag..])
+                                     .map(|(x, y)| x * y)
+                                     .sum::<f64>() / n as f64;
+        c / c0
+    }).collect()
+}
+
+/// The window of `autocorrelation_time` is cut at this multiple of the
+/// estimate, as proposed by Sokal.
+const WINDOW_FACTOR: f64 = 5.0;
+
+/// Estimates the integrated autocorrelation time
+/// `1/2 + sum_t rho(t)` in units of samples, so that uncorrelated samples
+/// give `1/2`, with Sokal's self-consistent window.
+pub fn autocorrelation_time(samples: &[f64]) -> f64 {
+    let n = samples.len();
+    if n < 2 {
+        return 0.5;
+    }
+    let mut max_lag = 64.min(n - 1);
+    loop {
+        let rho = autocorrelation(samples, max_lag);
+        let mut tau = 0.5;
+        for (lag, r) in rho.iter().enumerate().skip(1) {
+            tau += r;
+            if lag as f64 >= WINDOW_FACTOR * tau {
+                return tau.max(0.5);
+            }
+        }
+        if max_lag == n - 1 {
+            return tau.max(0.5);
+        }
+        max_lag = (2 * max_lag).min(n - 1);
+    }
+}
+
+/// Returns the index of the first sample after equilibration.
+///
+/// Following Chodera (2016), the cut-off is chosen among `num_candidates`
+/// evenly spaced indices in the first half of the series so as to maximize
+/// the number of effectively independent samples remaining after it.
+pub fn equilibration_index(samples: &[f64], num_candidates: usize) -> usize {
+    let n = samples.len();
+    if n < 4 || num_candidates == 0 {
+        return 0;
+    }
+    let stride = (n / 2 / num_candidates).max(1);
+    let mut best = (0, 0.0);
+    for start in (0..n / 2).step_by(stride) {
+        let rest = &samples[start..];
+        let effective = rest.len() as f64 / (2.0 * autocorrelation_time(rest)).max(1.0);
+        if effective > best.1 {
+            best = (start, effective);
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::Lcg;
+
+    /// An AR(1) process `x_{t+1} = phi x_t + e_t`, whose integrated
+    /// autocorrelation time is `(1 + phi) / (1 - phi) / 2`.
+    fn ar1(phi: f64, n: usize) -> Vec<f64> {
+        let mut rng = Lcg(42);
+        let mut x = 0.0;
+        (0..n).map(|_| {
+            x = phi * x + rng.normal();
+            x
+        }).collect()
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::new(&[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.mean, 2.5);
+        assert!((summary.variance - 5.0 / 3.0).abs() < 1e-12);
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 4.0);
+        assert!(Summary::new(&[]).is_none());
+    }
+
+    #[test]
+    fn test_uncorrelated_samples() {
+        let samples = ar1(0.0, 20000);
+        let tau = autocorrelation_time(&samples);
+        assert!((tau - 0.5).abs() < 0.1, "tau = {}", tau);
+
+        let naive = (variance(&samples) / samples.len() as f64).sqrt();
+        let error = block_error(&samples);
+        assert!(error >= naive && error < 1.5 * naive, "{} vs {}", error, naive);
+    }
+
+    #[test]
+    fn test_correlated_samples() {
+        let phi = 0.9;
+        let samples = ar1(phi, 100000);
+        let expected = (1.0 + phi) / (1.0 - phi) / 2.0;
+        let tau = autocorrelation_time(&samples);
+        assert!((tau - expected).abs() < 0.15 * expected, "tau = {}", tau);
+
+        // The error of the mean is underestimated by a factor sqrt(2 tau)
+        // unless the correlation is taken into account.
+        let naive = (variance(&samples) / samples.len() as f64).sqrt();
+        let ratio = block_error(&samples) / naive;
+        let expected = (2.0 * expected).sqrt();
+        assert!((ratio - expected).abs() < 0.25 * expected, "ratio = {}", ratio);
+    }
+
+    #[test]
+    fn test_equilibration_index() {
+        let mut samples = ar1(0.5, 5000);
+        for (i, x) in samples.iter_mut().enumerate().take(500) {
+            *x += 50.0 * (-(i as f64) / 50.0).exp();
+        }
+        let index = equilibration_index(&samples, 100);
+        assert!((150..=600).contains(&index), "index = {}", index);
+
+        assert_eq!(equilibration_index(&ar1(0.5, 5000), 100), 0);
+    }
+}
@@ -0,0 +1,325 @@
+use error::{Error, Result};
+use input::{starts_with_keyword, Entry, Input, Section};
+use std::str::FromStr;
+
+/// Values to sweep over, read from a `<<<< sweep ... >>>>` block such as
+///
+/// ```text
+/// <<<< sweep
+/// tempk = 300.0 310.0 320.0
+/// n_seed = 1 2 3
+/// ninfo = 1ubq.ninfo 1ubq_strong.ninfo
+/// n_tstep = 1000000
+/// >>>>
+/// ```
+///
+/// Each parameter lists whitespace-separated values; parameters which are
+/// not given are left as they are in the base input file. The ninfo files
+/// are relative to `path_natinfo` and replace the file of the only `NINFO`
+/// line, or of the `NINFO(1/1)` line with `ninfo(1/1) = ...`; only one such
+/// key may be given.
+pub struct Sweep {
+    pub tempk: Vec<f64>,
+    pub n_seed: Vec<i64>,
+    pub ninfo: Vec<String>,
+    /// The units of the `NINFO(...)` line to replace, e.g. `1/1`.
+    pub ninfo_units: Option<String>,
+    pub n_tstep: Vec<usize>,
+}
+
+fn parse_values<T>(value: &str) -> Result<Vec<T>>
+    where T: FromStr, Error: From<T::Err>
+{
+    value.split_whitespace().map(|v| v.parse().map_err(Error::from)).collect()
+}
+
+impl Sweep {
+    pub fn from_section(section: &Section) -> Result<Self> {
+        let mut sweep = Sweep {
+            tempk: Vec::new(),
+            n_seed: Vec::new(),
+            ninfo: Vec::new(),
+            ninfo_units: None,
+            n_tstep: Vec::new(),
+        };
+        let mut has_ninfo = false;
+        for entry in &section.entries {
+            match *entry {
+                Entry::Param(ref key, ref value) => match key.as_str() {
+                    "tempk" => sweep.tempk = parse_values(value)?,
+                    "n_seed" => sweep.n_seed = parse_values(value)?,
+                    "n_tstep" => sweep.n_tstep = parse_values(value)?,
+                    _ if key == "ninfo" || key.starts_with("ninfo(") && key.ends_with(')') => {
+                        // Only one NINFO line can be swept.
+                        if has_ninfo {
+                            return Err(Error::InvalidLine(entry.to_string()));
+                        }
+                        has_ninfo = true;
+                        if key != "ninfo" {
+                            sweep.ninfo_units = Some(key["ninfo(".len()..key.len() - 1].to_string());
+                        }
+                        sweep.ninfo = value.split_whitespace().map(|s| s.to_string()).collect();
+                    }
+                    _ => return Err(Error::InvalidLine(entry.to_string())),
+                },
+                Entry::Line(_) => return Err(Error::InvalidLine(entry.to_string())),
+            }
+        }
+        Ok(sweep)
+    }
+
+    /// Returns every combination of the swept values.
+    pub fn combinations(&self) -> Vec<Combination> {
+        fn axis<T: Clone>(values: &[T]) -> Vec<Option<T>> {
+            if values.is_empty() {
+                vec![None]
+            } else {
+                values.iter().cloned().map(Some).collect()
+            }
+        }
+
+        let mut combinations = Vec::new();
+        for tempk in axis(&self.tempk) {
+            for n_seed in axis(&self.n_seed) {
+                for ninfo in axis(&self.ninfo) {
+                    for n_tstep in axis(&self.n_tstep) {
+                        combinations.push(Combination {
+                            tempk,
+                            n_seed,
+                            ninfo: ninfo.clone(),
+                            ninfo_units: self.ninfo_units.clone(),
+                            n_tstep,
+                        });
+                    }
+                }
+            }
+        }
+        combinations
+    }
+}
+
+/// A single point of a `Sweep`.
+pub struct Combination {
+    pub tempk: Option<f64>,
+    pub n_seed: Option<i64>,
+    pub ninfo: Option<String>,
+    pub ninfo_units: Option<String>,
+    pub n_tstep: Option<usize>,
+}
+
+impl Combination {
+    /// Returns a name for each swept value, e.g. `["tempk_300", "n_seed_1"]`,
+    /// used for the directories and the `filename` of the run. A ninfo file
+    /// is named by its path without the extension, e.g. `ninfo_strong_1ubq`
+    /// for `strong/1ubq.ninfo`.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        if let Some(tempk) = self.tempk {
+            names.push(format!("tempk_{}", tempk));
+        }
+        if let Some(n_seed) = self.n_seed {
+            names.push(format!("n_seed_{}", n_seed));
+        }
+        if let Some(ref ninfo) = self.ninfo {
+            names.push(format!("ninfo_{}", path_name(ninfo)));
+        }
+        if let Some(n_tstep) = self.n_tstep {
+            names.push(format!("n_tstep_{}", n_tstep));
+        }
+        names
+    }
+
+    /// Applies the values to `input`, and points its output to `path` with a
+    /// `filename` suffixed by the names of the swept values.
+    pub fn apply(&self, input: &mut Input, path: &str) -> Result<()> {
+        let filename = {
+            let filenames = input.filenames()?;
+            let mut parts = vec![filenames.filename];
+            parts.extend(self.names());
+            parts.join("_")
+        };
+        let n_step_sim = input.md_information()?.n_step_sim;
+
+        {
+            let section = input.section_mut("filenames").expect("checked by filenames()");
+            section.set("path", path);
+            section.set("filename", filename);
+            if let Some(ref ninfo) = self.ninfo {
+                section.require_str("path_natinfo")?;
+                let keyword = match self.ninfo_units {
+                    Some(ref units) => format!("NINFO({})", units),
+                    None => "NINFO".to_string(),
+                };
+                let mut lines: Vec<_> = section.entries.iter_mut()
+                                               .filter_map(|entry| match *entry {
+                                                   Entry::Line(ref mut line)
+                                                       if starts_with_keyword(line, &keyword) => Some(line),
+                                                   _ => None,
+                                               })
+                                               .collect();
+                if lines.len() != 1 {
+                    return Err(Error::LineCount { keyword, found: lines.len() });
+                }
+                *lines[0] = replace_last_column(lines[0], ninfo);
+            }
+        }
+
+        let section = input.section_mut("md_information").expect("checked by md_information()");
+        if let Some(tempk) = self.tempk {
+            // Keep the decimal point so that the value still reads as a real.
+            section.set("tempk", format!("{:?}", tempk));
+        }
+        if let Some(n_seed) = self.n_seed {
+            section.set("n_seed", n_seed);
+        }
+        if let Some(n_tstep) = self.n_tstep {
+            for i in 1..n_step_sim + 1 {
+                section.set(&format!("n_tstep({})", i), n_tstep);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns `path` without its extension, with `/` replaced by `_` and `.`
+/// and empty components dropped.
+fn path_name(path: &str) -> String {
+    let stem = match path.rfind('.') {
+        Some(pos) if !path[pos..].contains('/') => &path[..pos],
+        _ => path,
+    };
+    let parts: Vec<_> = stem.split('/').filter(|part| !part.is_empty() && *part != ".").collect();
+    parts.join("_")
+}
+
+/// Replaces the file name of a `NINFO(all/all) file.ninfo` line.
+fn replace_last_column(line: &str, value: &str) -> String {
+    let line = line.trim_end();
+    match line.rfind(char::is_whitespace) {
+        Some(pos) => format!("{} {}", line[..pos].trim_end(), value),
+        None => format!("{} {}", line, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "\
+<<<< filenames
+path = ./data
+filename = md
+OUTPUT dcd
+path_natinfo = ./ninfo
+NINFO(all/all) 1ubq.ninfo
+>>>>
+
+<<<< md_information
+n_step_sim = 2
+n_tstep(1) = 1000
+n_tstep(2) = 2000
+tstep_size = 0.4
+n_step_save = 100
+tempk = 300.0
+n_seed = 1
+>>>>
+";
+
+    const SWEEP: &str = "\
+<<<< sweep
+tempk = 310.0 320.5
+n_seed = 1 2 3
+ninfo = strong/1ubq.ninfo
+>>>>
+";
+
+    fn load_sweep() -> Sweep {
+        let spec = Input::load(SWEEP.as_bytes()).unwrap();
+        Sweep::from_section(spec.section("sweep").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_combinations() {
+        let combinations = load_sweep().combinations();
+        assert_eq!(combinations.len(), 6);
+        assert_eq!(combinations[0].names(),
+                   vec!["tempk_310", "n_seed_1", "ninfo_strong_1ubq"]);
+        assert_eq!(combinations[5].names(),
+                   vec!["tempk_320.5", "n_seed_3", "ninfo_strong_1ubq"]);
+    }
+
+    #[test]
+    fn test_ninfo_names() {
+        let spec = Input::load("<<<< sweep\nninfo = strong/1ubq.ninfo weak/1ubq.ninfo \
+                                ./1ubq.go.ninfo 1ubq.ninfo\n>>>>\n".as_bytes()).unwrap();
+        let names: Vec<_> = Sweep::from_section(spec.section("sweep").unwrap()).unwrap()
+                                  .combinations()
+                                  .iter()
+                                  .map(|combination| combination.names().join("/"))
+                                  .collect();
+        assert_eq!(names, vec!["ninfo_strong_1ubq", "ninfo_weak_1ubq", "ninfo_1ubq.go", "ninfo_1ubq"]);
+    }
+
+    #[test]
+    fn test_apply_combination() {
+        let combination = Combination {
+            tempk: Some(320.5),
+            n_seed: Some(3),
+            ninfo: Some("strong/1ubq.ninfo".to_string()),
+            ninfo_units: None,
+            n_tstep: Some(5000),
+        };
+        let mut input = Input::load(INPUT.as_bytes()).unwrap();
+        combination.apply(&mut input, "out/tempk_320.5").unwrap();
+
+        let filenames = input.filenames().unwrap();
+        assert_eq!(filenames.path, "out/tempk_320.5");
+        assert_eq!(filenames.filename, "md_tempk_320.5_n_seed_3_ninfo_strong_1ubq_n_tstep_5000");
+        assert_eq!(filenames.ninfo, vec!["NINFO(all/all) strong/1ubq.ninfo"]);
+
+        let md_information = input.md_information().unwrap();
+        assert_eq!(md_information.tempk, Some(320.5));
+        assert_eq!(md_information.n_seed, Some(3));
+        assert_eq!(md_information.n_tstep, vec![5000, 5000]);
+    }
+
+    #[test]
+    fn test_apply_ninfo_of_units() {
+        let base = INPUT.replace("NINFO(all/all) 1ubq.ninfo",
+                                 "NINFO(1/1) 1ubq_a.ninfo\nninfo(1/2) 1ubq_ab.ninfo\nNINFO(2/2) 1ubq_b.ninfo");
+        let spec = Input::load("<<<< sweep\nninfo(1/2) = strong.ninfo\n>>>>\n".as_bytes()).unwrap();
+        let sweep = Sweep::from_section(spec.section("sweep").unwrap()).unwrap();
+        let mut input = Input::load(base.as_bytes()).unwrap();
+        sweep.combinations()[0].apply(&mut input, "out").unwrap();
+        assert_eq!(input.filenames().unwrap().ninfo,
+                   vec!["NINFO(1/1) 1ubq_a.ninfo", "ninfo(1/2) strong.ninfo", "NINFO(2/2) 1ubq_b.ninfo"]);
+
+        // Without the units, the line to replace is ambiguous.
+        let spec = Input::load("<<<< sweep\nninfo = strong.ninfo\n>>>>\n".as_bytes()).unwrap();
+        let sweep = Sweep::from_section(spec.section("sweep").unwrap()).unwrap();
+        let mut input = Input::load(base.as_bytes()).unwrap();
+        assert!(sweep.combinations()[0].apply(&mut input, "out").is_err());
+
+        // The files are relative to path_natinfo.
+        let mut input = Input::load(INPUT.replace("path_natinfo = ./ninfo\n", "").as_bytes()).unwrap();
+        assert!(sweep.combinations()[0].apply(&mut input, "out").is_err());
+    }
+
+    #[test]
+    fn test_unknown_sweep_parameter() {
+        let spec = Input::load("<<<< sweep\ntemperature = 300\n>>>>\n".as_bytes()).unwrap();
+        assert!(Sweep::from_section(spec.section("sweep").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_repeated_ninfo() {
+        for body in &["ninfo(1/1) = a.ninfo\nninfo(2/2) = b.ninfo",
+                      "ninfo = a.ninfo\nninfo(1/1) = b.ninfo"] {
+            let spec = Input::load(format!("<<<< sweep\n{}\n>>>>\n", body).as_bytes()).unwrap();
+            match Sweep::from_section(spec.section("sweep").unwrap()) {
+                Err(Error::InvalidLine(_)) => {}
+                _ => panic!("expected an invalid line"),
+            }
+        }
+    }
+}
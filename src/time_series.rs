@@ -1,54 +1,604 @@
 use error;
+use statistics;
+use melting::{Point, TwoState, TwoStateFit};
 use std::str::FromStr;
 use std::fmt;
+use std::io::BufRead;
+use std::rc::Rc;
+
+/// A value column of a time-series file, e.g. `tempk`.
+///
+/// `width` and `precision` are those of the written values, so that a
+/// SnapShot is written back in the same layout it was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub name:      String,
+    pub width:     usize,
+    pub precision: usize,
+}
+
+impl Column {
+    pub fn new(name: &str, width: usize, precision: usize) -> Self {
+        Column {
+            name: name.to_string(),
+            width,
+            precision,
+        }
+    }
+
+    /// Returns the columns written by CafeMol without energy decomposition.
+    pub fn defaults() -> Vec<Column> {
+        vec![Column::new("tempk",   8, 2),
+             Column::new("radg",    8, 2),
+             Column::new("etot",   10, 2),
+             Column::new("velet",  10, 2),
+             Column::new("qscore",  6, 3),
+             Column::new("rmsd",    8, 2)]
+    }
+}
+
+/// Returns the byte ranges of the whitespace-separated tokens of `line`.
+fn token_ranges(line: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                ranges.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        ranges.push((s, line.len()));
+    }
+    ranges
+}
+
+/// The tokens of a row, as byte ranges of the line.
+struct Row<'a> {
+    unit:   &'a str,
+    step:   (usize, usize),
+    values: Vec<(usize, usize)>,
+}
+
+fn split_row(line: &str) -> error::Result<Row<'_>> {
+    let mut ranges = token_ranges(line);
+    let unit = if line.starts_with('#') && !ranges.is_empty() {
+        let (start, end) = ranges.remove(0);
+        &line[start..end]
+    } else {
+        ""
+    };
+    if ranges.is_empty() {
+        return Err(error::Error::InvalidLine(line.to_string()));
+    }
+    let step = ranges.remove(0);
+    Ok(Row { unit, step, values: ranges })
+}
+
+/// Returns the columns of a time-series file, named by the header line such as
+/// `#unit  step  tempk  radg ...` and laid out as in `row`, the first row.
+///
+/// The default column names are used if the header has no such line.
+pub fn columns_from(header: &[String], row: &str) -> error::Result<Vec<Column>> {
+    let names = header.iter().rev().filter_map(|line| {
+        let mut names: Vec<_> = line.trim_start_matches('#').split_whitespace().collect();
+        if names.first() == Some(&"unit") {
+            names.remove(0);
+        }
+        if names.first() == Some(&"step") {
+            Some(names[1..].iter().map(|name| name.to_string()).collect::<Vec<_>>())
+        } else {
+            None
+        }
+    }).next();
+    let names = match names {
+        Some(names) => names,
+        None => Column::defaults().into_iter().map(|column| column.name).collect(),
+    };
+
+    let tokens = split_row(row)?;
+    if tokens.values.len() != names.len() {
+        return Err(error::Error::ColumnCount {
+            expected: names.len(),
+            found: tokens.values.len(),
+        });
+    }
+    let mut prev_end = tokens.step.1;
+    Ok(names.iter().zip(tokens.values).map(|(name, (start, end))| {
+        let token = &row[start..end];
+        let width = end - prev_end - 1;
+        prev_end = end;
+        let precision = token.find('.').map_or(0, |pos| token.len() - pos - 1);
+        Column::new(name, width, precision)
+    }).collect())
+}
 
 /// A SnapShot contains the data for each time-step.
+#[derive(Clone)]
 pub struct SnapShot {
-    pub unit:   String,
-    pub step:   i32,
-    pub tempk:  f32,
-    pub radg:   f32,
-    pub etot:   f32,
-    pub velet:  f32,
-    pub qscore: f32,
-    pub rmsd:   f32,
+    pub unit:    String,
+    pub step:    i32,
+    /// The values of each of `columns`.
+    pub values:  Vec<f32>,
+    pub columns: Rc<Vec<Column>>,
+}
+
+impl SnapShot {
+    /// Parses a row whose values are laid out as `columns`.
+    pub fn parse_with(line: &str, columns: &Rc<Vec<Column>>) -> error::Result<Self> {
+        let tokens = split_row(line)?;
+        if tokens.values.len() != columns.len() {
+            return Err(error::Error::ColumnCount {
+                expected: columns.len(),
+                found: tokens.values.len(),
+            });
+        }
+        let mut values = Vec::with_capacity(columns.len());
+        for (start, end) in tokens.values {
+            values.push(line[start..end].parse()?);
+        }
+        Ok(SnapShot {
+            unit: tokens.unit.to_string(),
+            step: line[tokens.step.0..tokens.step.1].parse()?,
+            values,
+            columns: columns.clone(),
+        })
+    }
+
+    /// Returns the value of the column `name`, if any.
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.columns.iter()
+            .position(|column| column.name == name)
+            .map(|i| self.values[i])
+    }
 }
 
 impl FromStr for SnapShot {
     type Err = error::Error;
 
+    /// Parses a row of the default columns.
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        Ok(SnapShot {
-            unit:   line[ 0.. 6].trim().to_string(),
-            step:   line[ 6..16].trim().parse()?,
-            tempk:  line[17..25].trim().parse()?,
-            radg:   line[26..34].trim().parse()?,
-            etot:   line[35..45].trim().parse()?,
-            velet:  line[46..56].trim().parse()?,
-            qscore: line[57..63].trim().parse()?,
-            rmsd:   line[64..72].trim().parse()?,
-        })
+        SnapShot::parse_with(line, &Rc::new(Column::defaults()))
     }
 }
 
 impl fmt::Display for SnapShot {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:5} {:10} {:8.2} {:8.2} {:10.2} {:10.2} {:6.3} {:8.2}",
-               self.unit,
-               self.step,
-               self.tempk,
-               self.radg,
-               self.etot,
-               self.velet,
-               self.qscore,
-               self.rmsd)
+        write!(f, "{:5} {:10}", self.unit, self.step)?;
+        for (column, value) in self.columns.iter().zip(&self.values) {
+            write!(f, " {:w$.p$}", value, w = column.width, p = column.precision)?;
+        }
+        Ok(())
+    }
+}
+
+impl SnapShot {
+    /// Returns the unit number of a per-unit row (`#1`, `#2`, ...), or
+    /// `None` for the row of the whole system.
+    pub fn unit_index(&self) -> Option<usize> {
+        if self.unit.starts_with('#') {
+            self.unit[1..].parse().ok()
+        } else {
+            None
+        }
+    }
+}
+
+/// The rows written at a single step: one for the whole system followed by
+/// one for each unit.
+#[derive(Clone)]
+pub struct TimeStep {
+    pub step: i32,
+    pub system: SnapShot,
+    pub units: Vec<SnapShot>,
+}
+
+impl TimeStep {
+    pub fn unit(&self, unit: usize) -> Option<&SnapShot> {
+        self.units.iter().find(|snapshot| snapshot.unit_index() == Some(unit))
+    }
+}
+
+fn is_unit_row(line: &str) -> bool {
+    line.starts_with('#') && line[1..].starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn is_header(line: &str) -> bool {
+    line.trim().is_empty() || (line.starts_with('#') && !is_unit_row(line))
+}
+
+/// Parses a time series line by line, such as from a file still being
+/// written.
+pub struct Parser {
+    /// The header lines preceding the first row, kept verbatim.
+    pub header: Vec<String>,
+    columns: Option<Rc<Vec<Column>>>,
+    /// The step whose unit rows may still follow.
+    current: Option<TimeStep>,
+    line_number: usize,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser {
+            header: Vec::new(),
+            columns: None,
+            current: None,
+            line_number: 0,
+        }
+    }
+
+    /// Returns the columns, once the first row has been parsed.
+    pub fn columns(&self) -> Option<&Rc<Vec<Column>>> {
+        self.columns.as_ref()
+    }
+
+    /// Parses the next line, returning the previous step once the row of
+    /// the next one shows that all its unit rows were read.
+    ///
+    /// A malformed row is skipped with an error carrying its line number.
+    pub fn push(&mut self, line: &str) -> error::Result<Option<TimeStep>> {
+        self.line_number += 1;
+        if self.columns.is_none() && self.current.is_none() && is_header(line) {
+            self.header.push(line.to_string());
+            return Ok(None);
+        }
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let line_number = self.line_number;
+        let snapshot = match self.columns {
+            Some(ref columns) => SnapShot::parse_with(line, columns),
+            None => columns_from(&self.header, line).and_then(|found| {
+                let found = Rc::new(found);
+                let snapshot = SnapShot::parse_with(line, &found)?;
+                self.columns = Some(found);
+                Ok(snapshot)
+            }),
+        }.map_err(|err| err.at_line(line_number))?;
+
+        if snapshot.unit.is_empty() {
+            let time_step = TimeStep {
+                step: snapshot.step,
+                system: snapshot,
+                units: Vec::new(),
+            };
+            return Ok(self.current.replace(time_step));
+        }
+        match self.current {
+            Some(ref mut current) if current.step == snapshot.step => {
+                current.units.push(snapshot);
+                Ok(None)
+            }
+            _ => Err(error::Error::InvalidLine(line.to_string()).at_line(line_number)),
+        }
+    }
+
+    /// Returns the step whose unit rows may still follow, without taking it.
+    pub fn peek(&self) -> Option<&TimeStep> {
+        self.current.as_ref()
+    }
+
+    /// Returns the last step, whose unit rows may be incomplete if the file
+    /// is still being written.
+    pub fn finish(&mut self) -> Option<TimeStep> {
+        self.current.take()
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+/// The steps and units kept by `TimeSeries::select`.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// The first step to keep.
+    pub from: Option<i32>,
+    /// The last step to keep.
+    pub to: Option<i32>,
+    /// Keeps every `stride`-th of the steps in range; 0 is the same as 1.
+    pub stride: usize,
+    /// The units whose rows are kept; the row of the whole system is always
+    /// kept.
+    pub units: Option<Vec<usize>>,
+}
+
+impl Selection {
+    fn contains(&self, step: i32) -> bool {
+        self.from.into_iter().all(|from| step >= from) && self.to.into_iter().all(|to| step <= to)
+    }
+
+    fn keeps(&self, snapshot: &SnapShot) -> bool {
+        match self.units {
+            Some(ref units) => snapshot.unit_index().is_some_and(|unit| units.contains(&unit)),
+            None => true,
+        }
+    }
+}
+
+/// The largest spread of tempk, in K, of a run at one temperature.
+pub const TEMPERATURE_TOLERANCE: f64 = 0.05;
+
+/// A TimeSeries file contains trajectory data of CafeMol
+pub struct TimeSeries {
+    /// The header lines preceding the first row, kept verbatim.
+    pub header: Vec<String>,
+    /// The value columns shared by every SnapShot.
+    pub columns: Rc<Vec<Column>>,
+    pub steps: Vec<TimeStep>,
+}
+
+impl TimeSeries {
+    pub fn load<R: BufRead>(reader: R) -> error::Result<Self> {
+        TimeSeries::load_with(reader, Err)
+    }
+
+    /// Loads a time series, passing each malformed row to `on_error`, which
+    /// either skips it by returning `Ok(())` or stops loading with an error.
+    ///
+    /// The errors carry the line numbers of the rows.
+    pub fn load_with<R, F>(reader: R, mut on_error: F) -> error::Result<Self>
+        where R: BufRead, F: FnMut(error::Error) -> error::Result<()>
+    {
+        let mut parser = Parser::new();
+        let mut steps = Vec::new();
+        for line in reader.lines() {
+            match parser.push(&line?) {
+                Ok(Some(time_step)) => steps.push(time_step),
+                Ok(None) => {}
+                Err(err) => on_error(err)?,
+            }
+        }
+        steps.extend(parser.finish());
+
+        Ok(TimeSeries {
+            header: parser.header,
+            columns: parser.columns.unwrap_or_else(|| Rc::new(Column::defaults())),
+            steps,
+        })
+    }
+
+    /// Returns the index of the column `name` in `SnapShot::values`.
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.name == name)
+    }
+
+    /// Returns the indices of the columns `names`, failing on an unknown one.
+    pub fn columns_of<S: AsRef<str>>(&self, names: &[S]) -> error::Result<Vec<usize>> {
+        names.iter().map(|name| {
+            let name = name.as_ref();
+            self.column(name).ok_or_else(|| error::Error::UnknownColumn(name.to_string()))
+        }).collect()
+    }
+
+    pub fn get(&self, step: i32) -> Option<&TimeStep> {
+        self.steps.iter().find(|time_step| time_step.step == step)
+    }
+
+    /// Returns the rows of `unit` at every step.
+    pub fn unit<'a>(&'a self, unit: usize) -> impl Iterator<Item = &'a SnapShot> + 'a {
+        self.steps.iter().filter_map(move |time_step| time_step.unit(unit))
+    }
+
+    /// Returns the steps and values of the column `name`, for the whole
+    /// system or for `unit`.
+    pub fn series(&self, name: &str, unit: Option<usize>) -> error::Result<(Vec<i32>, Vec<f64>)> {
+        let column = self.columns_of(&[name])?[0];
+        let mut steps = Vec::new();
+        let mut values = Vec::new();
+        for time_step in &self.steps {
+            let snapshot = match unit {
+                Some(unit) => match time_step.unit(unit) {
+                    Some(snapshot) => snapshot,
+                    None => continue,
+                },
+                None => &time_step.system,
+            };
+            steps.push(time_step.step);
+            values.push(snapshot.values[column] as f64);
+        }
+        Ok((steps, values))
+    }
+
+    /// Returns the temperature of a run at one temperature, the mean tempk
+    /// of the whole system, failing if tempk varies by more than
+    /// `TEMPERATURE_TOLERANCE` as across the exchanges of a replica.
+    pub fn temperature(&self) -> error::Result<f64> {
+        let (_, temperatures) = self.series("tempk", None)?;
+        if temperatures.is_empty() {
+            return Err(error::Error::NoSamples("tempk".to_string()));
+        }
+        let min = temperatures.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = temperatures.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if max - min > TEMPERATURE_TOLERANCE {
+            return Err(error::Error::VaryingTemperature { min, max });
+        }
+        Ok(statistics::mean(&temperatures))
     }
+
+    fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|column| column.name.clone()).collect()
+    }
+
+    /// Checks that `other` was written like this series, with the same
+    /// header lines and units in the first step. Differences in whitespace
+    /// and in lines of only `#` are ignored.
+    pub fn check_header(&self, other: &TimeSeries) -> error::Result<()> {
+        fn significant(header: &[String]) -> Vec<String> {
+            header.iter()
+                  .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+                  .filter(|line| !line.chars().all(|c| c == '#' || c == ' '))
+                  .collect()
+        }
+        let (expected, found) = (significant(&self.header), significant(&other.header));
+        let length = expected.len().max(found.len());
+        if let Some(i) = (0..length).find(|&i| expected.get(i) != found.get(i)) {
+            return Err(error::Error::HeaderMismatch {
+                expected: expected.get(i).cloned().unwrap_or_default(),
+                found: found.get(i).cloned().unwrap_or_default(),
+            });
+        }
+
+        let units = |ts: &TimeSeries| ts.steps.first().map(|time_step| {
+            time_step.units.iter().map(|snapshot| snapshot.unit.clone()).collect::<Vec<_>>()
+        });
+        if let (Some(expected), Some(found)) = (units(self), units(other)) {
+            if expected != found {
+                return Err(error::Error::UnitMismatch { expected, found });
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends `other`, the continuation of a restarted run. Only the
+    /// columns are checked; see `check_header` for the rest of the header.
+    ///
+    /// The steps here from the first step of `other` on are dropped, since
+    /// a restarted run repeats the step it restarts from and a crashed run
+    /// may have written steps beyond it. With `renumber`, the steps of
+    /// `other` are first shifted to start at the last step here, for runs
+    /// whose step counter restarts from zero.
+    ///
+    /// Returns the number of dropped steps.
+    pub fn append(&mut self, mut other: TimeSeries, renumber: bool) -> error::Result<usize> {
+        if self.column_names() != other.column_names() {
+            return Err(error::Error::ColumnMismatch {
+                expected: self.column_names(),
+                found: other.column_names(),
+            });
+        }
+
+        let (first, last, next) = match (self.steps.first(), self.steps.last(), other.steps.first()) {
+            (Some(first), Some(last), Some(next)) => (first.step, last.step, next.step),
+            _ => {
+                self.steps.append(&mut other.steps);
+                return Ok(0);
+            }
+        };
+
+        let next = if renumber {
+            let offset = last - next;
+            for time_step in &mut other.steps {
+                time_step.step += offset;
+                time_step.system.step += offset;
+                for snapshot in &mut time_step.units {
+                    snapshot.step += offset;
+                }
+            }
+            last
+        } else if next <= first && self.steps.len() > 1 {
+            return Err(error::Error::NonMonotonicSteps { previous: last, step: next });
+        } else {
+            next
+        };
+
+        let num_steps = self.steps.len();
+        self.steps.retain(|time_step| time_step.step < next);
+        let dropped = num_steps - self.steps.len();
+
+        for time_step in &mut other.steps {
+            let columns = self.columns.clone();
+            time_step.system.columns = columns.clone();
+            for snapshot in &mut time_step.units {
+                snapshot.columns = columns.clone();
+            }
+        }
+        self.steps.append(&mut other.steps);
+        Ok(dropped)
+    }
+
+    /// Returns a copy with the header and the steps and units of `selection`.
+    pub fn select(&self, selection: &Selection) -> TimeSeries {
+        let steps = self.steps.iter()
+            .filter(|time_step| selection.contains(time_step.step))
+            .step_by(selection.stride.max(1))
+            .map(|time_step| TimeStep {
+                step: time_step.step,
+                system: time_step.system.clone(),
+                units: time_step.units.iter()
+                                      .filter(|snapshot| selection.keeps(snapshot))
+                                      .cloned()
+                                      .collect(),
+            })
+            .collect();
+        TimeSeries {
+            header: self.header.clone(),
+            columns: self.columns.clone(),
+            steps,
+        }
+    }
+
+    /// Returns the mean tempk of the whole system and the mean of `name`
+    /// with its block error, or with `threshold`, the fraction of the steps
+    /// at which `name` is at least `threshold`.
+    pub fn melting_point(&self, name: &str, unit: Option<usize>,
+                         threshold: Option<f64>) -> error::Result<Point> {
+        let (_, temperatures) = self.series("tempk", None)?;
+        let (_, mut values) = self.series(name, unit)?;
+        if temperatures.is_empty() || values.is_empty() {
+            return Err(error::Error::NoSamples(name.to_string()));
+        }
+        if let Some(threshold) = threshold {
+            for value in &mut values {
+                *value = if *value >= threshold { 1.0 } else { 0.0 };
+            }
+        }
+        Ok(Point {
+            temperature: statistics::mean(&temperatures),
+            value: statistics::mean(&values),
+            error: Some(statistics::block_error(&values)),
+        })
+    }
+
+    pub fn num_units(&self) -> usize {
+        self.steps.iter()
+            .flat_map(|time_step| time_step.units.iter())
+            .filter_map(SnapShot::unit_index)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Fits a two-state model to the melting curve of runs at several
+/// temperatures, one point per run as given by `TimeSeries::melting_point`
+/// at the temperature paired with the run.
+///
+/// Returns the points with the fit, which is `None` if they cannot be
+/// fitted; see `TwoState::fit`.
+pub fn fit_melting_curve(runs: &[(TimeSeries, f64)], name: &str, unit: Option<usize>,
+                         threshold: Option<f64>, sloped: bool)
+                         -> error::Result<(Vec<Point>, Option<TwoStateFit>)> {
+    let points = runs.iter()
+                     .map(|&(ref ts, temperature)| {
+                         ts.melting_point(name, unit, threshold).map(|point| Point { temperature, ..point })
+                     })
+                     .collect::<error::Result<Vec<_>>>()?;
+    let fit = TwoState::fit(&points, sloped);
+    Ok((points, fit))
 }
 
-// /// A TimeSeries file contains trajectory data of CafeMol
-// pub struct TimeSeries {
-//     pub snapshots: Vec<SnapShot>,
-// }
+impl fmt::Display for TimeSeries {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.header {
+            writeln!(f, "{}", line)?;
+        }
+        for time_step in &self.steps {
+            writeln!(f, "{}", time_step.system)?;
+            for snapshot in &time_step.units {
+                writeln!(f, "{}", snapshot)?;
+            }
+        }
+        Ok(())
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -60,13 +610,281 @@ mod tests {
         let snapshot = line.parse::<SnapShot>().unwrap();
         assert_eq!(snapshot.unit,   "".to_string());
         assert_eq!(snapshot.step,   0);
-        assert_eq!(snapshot.tempk,  360.0);
-        assert_eq!(snapshot.radg,   366.38);
-        assert_eq!(snapshot.etot,   33.93);
-        assert_eq!(snapshot.velet,  377.23);
-        assert_eq!(snapshot.qscore, 0.0);
-        assert_eq!(snapshot.rmsd,   732.77);
+        assert_eq!(snapshot.get("tempk"), Some(360.0));
+        assert_eq!(snapshot.get("radg"), Some(366.38));
+        assert_eq!(snapshot.get("etot"), Some(33.93));
+        assert_eq!(snapshot.get("velet"), Some(377.23));
+        assert_eq!(snapshot.get("qscore"), Some(0.0));
+        assert_eq!(snapshot.get("rmsd"), Some(732.77));
 
         assert_eq!(&snapshot.to_string(), line);
     }
+
+    const TIME_SERIES: &str = "\
+#########################################################
+#                                                       #
+#              CafeMol time-series file                 #
+#                                                       #
+#########################################################
+# tempk: temperature, radg: radius of gyration
+# etot: total energy, velet: kinetic energy
+#unit       step    tempk     radg       etot      velet qscore     rmsd
+#########################################################
+               0   300.00    25.31     -83.12     123.45  0.986     0.00
+#1             0   300.00    13.14     -40.10      60.12  0.990     0.00
+#2             0   300.00    13.20     -43.02      63.33  0.982     0.00
+            1000   301.25    25.80     -80.06     125.01  0.951     1.02
+#1          1000   301.25    13.50     -38.88      61.00  0.960     0.98
+#2          1000   301.25    13.31     -41.18      64.01  0.943     1.05
+";
+
+    #[test]
+    fn test_load_time_series() {
+        let ts = TimeSeries::load(TIME_SERIES.as_bytes()).unwrap();
+        assert_eq!(ts.header.len(), 9);
+        assert_eq!(ts.steps.len(), 2);
+        assert_eq!(ts.num_units(), 2);
+
+        let time_step = ts.get(1000).unwrap();
+        assert_eq!(time_step.system.get("radg"), Some(25.80));
+        assert_eq!(time_step.units.len(), 2);
+        assert_eq!(time_step.unit(2).unwrap().get("qscore"), Some(0.943));
+        assert!(time_step.unit(3).is_none());
+        assert!(ts.get(500).is_none());
+
+        let (steps, radg) = ts.series("radg", Some(2)).unwrap();
+        assert_eq!(steps, vec![0, 1000]);
+        assert_eq!(radg, vec![13.20f32 as f64, 13.31f32 as f64]);
+        assert!(ts.series("radius", None).is_err());
+
+        let qscore = ts.column("qscore").unwrap();
+        let qscores: Vec<_> = ts.unit(1).map(|snapshot| snapshot.values[qscore]).collect();
+        assert_eq!(qscores, vec![0.990, 0.960]);
+
+        assert_eq!(ts.to_string(), TIME_SERIES);
+    }
+
+    #[test]
+    fn test_load_decomposed_columns() {
+        let text = "\
+#unit       step    tempk     radg       etot      velet qscore     rmsd      local         go  repulsive
+               0   300.00    25.31     -83.12     123.45  0.986     0.00      12.50    -102.31       6.69
+            1000   301.25    25.80     -80.06     125.01  0.951     1.02      13.01     -99.74       6.67
+";
+        let ts = TimeSeries::load(text.as_bytes()).unwrap();
+        let names: Vec<_> = ts.columns.iter().map(|column| column.name.as_str()).collect();
+        assert_eq!(names, vec!["tempk", "radg", "etot", "velet", "qscore", "rmsd",
+                               "local", "go", "repulsive"]);
+        assert_eq!(ts.columns[8], Column::new("repulsive", 10, 2));
+
+        let snapshot = &ts.get(1000).unwrap().system;
+        assert_eq!(snapshot.get("go"), Some(-99.74));
+        assert_eq!(snapshot.get("qscore"), Some(0.951));
+        assert_eq!(snapshot.get("electrostatic"), None);
+
+        assert_eq!(ts.to_string(), text);
+    }
+
+    #[test]
+    fn test_column_count_mismatch() {
+        let text = "#unit       step    tempk\n               0   300.00    25.31\n";
+        assert!(TimeSeries::load(text.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_malformed_rows() {
+        let text = "\
+#unit       step    tempk     radg       etot      velet qscore     rmsd
+               0   300.00    25.31     -83.12     123.45  0.986     0.00
+            1000   301.25    25.80     -80.06
+            2000   301.25    25.80     -80.06     125.01  0.9x1     1.02
+            3000   302.00    25.11     -81.00     124.00  0.960     0.50
+";
+        match TimeSeries::load(text.as_bytes()) {
+            Err(error::Error::AtLine { line, ref error }) => {
+                assert_eq!(line, 3);
+                match **error {
+                    error::Error::ColumnCount { expected, found } => {
+                        assert_eq!(expected, 6);
+                        assert_eq!(found, 3);
+                    }
+                    _ => panic!("expected a column count error"),
+                }
+            }
+            _ => panic!("expected an error at line 3"),
+        }
+
+        let mut lines = Vec::new();
+        let ts = TimeSeries::load_with(text.as_bytes(), |err| {
+            if let error::Error::AtLine { line, .. } = err {
+                lines.push(line);
+            }
+            Ok(())
+        }).unwrap();
+        assert_eq!(lines, vec![3, 4]);
+        let steps: Vec<_> = ts.steps.iter().map(|time_step| time_step.step).collect();
+        assert_eq!(steps, vec![0, 3000]);
+    }
+
+    #[test]
+    fn test_parse_short_or_multibyte_line() {
+        assert!("               0   360.00".parse::<SnapShot>().is_err());
+        assert!("#１            0   360.00   366.38      33.93     377.23  0.000   732.77".parse::<SnapShot>().is_ok());
+        assert!("    ０   360.00   366.38      33.93     377.23  0.000   732.77".parse::<SnapShot>().is_err());
+    }
+
+    fn load_steps(steps: &[i32]) -> TimeSeries {
+        let mut text = "#unit       step    tempk     radg       etot      velet qscore     rmsd\n".to_string();
+        for step in steps {
+            text += &format!("      {:10}   300.00    25.31     -83.12     123.45  {:.3}     0.00\n",
+                             step, *step as f32 / 10000.0);
+            text += &format!("#1    {:10}   300.00    25.31     -83.12     123.45  {:.3}     0.00\n",
+                             step, *step as f32 / 10000.0);
+        }
+        TimeSeries::load(text.as_bytes()).unwrap()
+    }
+
+    fn steps_of(ts: &TimeSeries) -> Vec<i32> {
+        ts.steps.iter().map(|time_step| time_step.step).collect()
+    }
+
+    #[test]
+    fn test_append_restart() {
+        let mut ts = load_steps(&[0, 1000, 2000]);
+        assert_eq!(ts.append(load_steps(&[2000, 3000]), false).unwrap(), 1);
+        assert_eq!(steps_of(&ts), vec![0, 1000, 2000, 3000]);
+
+        // A run crashed after step 3000 and was restarted from step 1000.
+        let mut crashed = load_steps(&[0, 1000, 2000, 3000]);
+        assert_eq!(crashed.append(load_steps(&[1000, 2000, 3000, 4000]), false).unwrap(), 3);
+        assert_eq!(steps_of(&crashed), vec![0, 1000, 2000, 3000, 4000]);
+    }
+
+    #[test]
+    fn test_append_renumbered() {
+        let mut ts = load_steps(&[0, 1000, 2000]);
+        assert!(ts.append(load_steps(&[0, 1000]), false).is_err());
+        assert_eq!(ts.append(load_steps(&[0, 1000, 2000]), true).unwrap(), 1);
+        assert_eq!(steps_of(&ts), vec![0, 1000, 2000, 3000, 4000]);
+
+        let last = ts.steps.last().unwrap();
+        assert_eq!(last.system.step, 4000);
+        assert_eq!(last.units[0].step, 4000);
+        assert_eq!(last.system.get("qscore"), Some(0.2));
+    }
+
+    #[test]
+    fn test_append_with_other_columns() {
+        let mut ts = load_steps(&[0, 1000]);
+        let text = "#unit       step    tempk\n            1000   300.00\n";
+        let other = TimeSeries::load(text.as_bytes()).unwrap();
+        assert!(ts.append(other, false).is_err());
+    }
+
+    #[test]
+    fn test_check_header() {
+        let ts = TimeSeries::load(TIME_SERIES.as_bytes()).unwrap();
+        let reformatted = TIME_SERIES.replace("#########################################################\n#  ",
+                                              "###\n\n#  ");
+        assert!(ts.check_header(&TimeSeries::load(reformatted.as_bytes()).unwrap()).is_ok());
+
+        let other = TIME_SERIES.replace("velet: kinetic energy", "velet: kinetic energy (kcal/mol)");
+        let err = ts.check_header(&TimeSeries::load(other.as_bytes()).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "expected header line '# etot: total energy, velet: kinetic energy', \
+                                     found '# etot: total energy, velet: kinetic energy (kcal/mol)'");
+
+        let one_unit: String = TIME_SERIES.lines()
+                                          .filter(|line| !line.starts_with("#2"))
+                                          .map(|line| format!("{}\n", line))
+                                          .collect();
+        assert!(ts.check_header(&TimeSeries::load(one_unit.as_bytes()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_melting_point() {
+        let ts = TimeSeries::load(TIME_SERIES.as_bytes()).unwrap();
+        let point = ts.melting_point("qscore", None, None).unwrap();
+        assert!((point.temperature - 300.625).abs() < 1e-4);
+        assert!((point.value - 0.9685).abs() < 1e-6);
+        let point = ts.melting_point("qscore", Some(2), Some(0.95)).unwrap();
+        assert_eq!(point.value, 0.5);
+        assert!(ts.melting_point("qscore", Some(3), None).is_err());
+    }
+
+    #[test]
+    fn test_fit_melting_curve() {
+        let runs: Vec<_> = [290.0, 310.0].iter()
+                                         .map(|&t| (TimeSeries::load(TIME_SERIES.as_bytes()).unwrap(), t))
+                                         .collect();
+        let (points, fit) = fit_melting_curve(&runs, "qscore", None, None, true).unwrap();
+        let temperatures: Vec<_> = points.iter().map(|point| point.temperature).collect();
+        assert_eq!(temperatures, vec![290.0, 310.0]);
+        assert!(fit.is_none());
+    }
+
+    #[test]
+    fn test_temperature() {
+        let ts = TimeSeries::load(TIME_SERIES.as_bytes()).unwrap();
+        match ts.temperature() {
+            Err(error::Error::VaryingTemperature { min, max }) => {
+                assert_eq!((min, max), (300.0, 301.25));
+            }
+            _ => panic!("expected a varying temperature"),
+        }
+        let constant = TIME_SERIES.replace("301.25", "300.00");
+        let ts = TimeSeries::load(constant.as_bytes()).unwrap();
+        assert_eq!(ts.temperature().unwrap(), 300.0);
+    }
+
+    #[test]
+    fn test_select() {
+        let ts = load_steps(&[0, 1000, 2000, 3000, 4000, 5000]);
+        let selection = Selection { from: Some(1000), to: Some(4500), stride: 2, units: None };
+        let selected = ts.select(&selection);
+        assert_eq!(steps_of(&selected), vec![1000, 3000]);
+        assert_eq!(selected.steps[0].units.len(), 1);
+
+        let selected = ts.select(&Selection { units: Some(vec![2]), ..Selection::default() });
+        assert_eq!(steps_of(&selected), steps_of(&ts));
+        assert!(selected.steps.iter().all(|time_step| time_step.units.is_empty()));
+
+        // The selection round-trips through the native format.
+        let text = ts.select(&selection).to_string();
+        let reloaded = TimeSeries::load(text.as_bytes()).unwrap();
+        assert_eq!(reloaded.to_string(), text);
+        assert_eq!(steps_of(&reloaded), vec![1000, 3000]);
+    }
+
+    #[test]
+    fn test_parse_incrementally() {
+        let mut parser = Parser::new();
+        let mut steps = Vec::new();
+        for line in TIME_SERIES.lines().take(12) {
+            steps.extend(parser.push(line).unwrap());
+        }
+        // The unit rows of step 0 may still follow.
+        assert!(steps.is_empty());
+        assert_eq!(parser.peek().map(|pending| pending.step), Some(0));
+        assert_eq!(parser.header.len(), 9);
+        assert_eq!(parser.columns().unwrap().len(), 6);
+
+        for line in TIME_SERIES.lines().skip(12) {
+            steps.extend(parser.push(line).unwrap());
+        }
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].units.len(), 2);
+        let last = parser.finish().unwrap();
+        assert_eq!(last.step, 1000);
+        assert_eq!(last.units.len(), 2);
+        assert!(parser.finish().is_none());
+
+        let err = parser.push("#1          2000   301.25    13.50").err().unwrap();
+        assert_eq!(err.to_string().split(':').next(), Some("line 16"));
+    }
+
+    #[test]
+    fn test_unit_row_without_system_row() {
+        let text = "#unit       step\n#1             0   300.00    13.14     -40.10      60.12  0.990     0.00\n";
+        assert!(TimeSeries::load(text.as_bytes()).is_err());
+    }
 }
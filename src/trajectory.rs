@@ -0,0 +1,491 @@
+//! Trajectories of one or more DCD files, read as a single sequence of
+//! frames with random access.
+//!
+//! A frame is located by its offset from the end of the header, since every
+//! frame of a file without fixed atoms has the same size. The frames are
+//! counted from the length of the file, so that the complete frames of a
+//! file still being written are included.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
+use error::{Error, Result};
+use geometry::Vector3d;
+use time_series::{TimeSeries, TimeStep};
+
+/// A frame of a trajectory.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The index of the frame in the trajectory.
+    pub index: usize,
+    pub step: usize,
+    pub time: f64,
+    pub positions: Vec<Vector3d>,
+}
+
+/// The header of a DCD file with the layout of its frames.
+#[derive(Clone, Debug)]
+struct Header {
+    big_endian: bool,
+    start_step: usize,
+    step_interval: usize,
+    delta: f32,
+    num_particles: usize,
+    /// The length of the header in bytes.
+    length: u64,
+    /// Whether each frame starts with a record of the unit cell.
+    unit_cell: bool,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Header {
+    fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut marker = [0; 4];
+        reader.read_exact(&mut marker)?;
+        let big_endian = match (u32::from_le_bytes(marker), u32::from_be_bytes(marker)) {
+            (84, _) => false,
+            (_, 84) => true,
+            _ => return Err(invalid_data("not a DCD file")),
+        };
+        let int = |bytes: &[u8]| {
+            let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+        };
+
+        // "CORD", twenty control integers and the closing marker.
+        let mut control = [0; 88];
+        reader.read_exact(&mut control)?;
+        if &control[..4] != b"CORD" {
+            return Err(invalid_data("not a DCD file of coordinates"));
+        }
+        let control: Vec<_> = control[4..84].chunks(4).map(int).collect();
+        if control[8] != 0 {
+            return Err(invalid_data("DCD files with fixed atoms are not supported"));
+        }
+
+        reader.read_exact(&mut marker)?;
+        let title_length = int(&marker) as u64;
+        io::copy(&mut reader.take(title_length + 4), &mut io::sink())?;
+
+        let mut atoms = [0; 12];
+        reader.read_exact(&mut atoms)?;
+        Ok(Header {
+            big_endian,
+            start_step: control[1] as usize,
+            step_interval: control[2] as usize,
+            delta: f32::from_bits(control[9]),
+            num_particles: int(&atoms[4..8]) as usize,
+            length: 4 + 88 + 4 + title_length + 4 + 12,
+            unit_cell: control[10] != 0,
+        })
+    }
+
+    /// Returns the length of a frame in bytes: an optional unit cell record
+    /// of six doubles and a record per axis.
+    fn frame_length(&self) -> u64 {
+        let unit_cell = if self.unit_cell { 4 + 48 + 4 } else { 0 };
+        unit_cell + 3 * (4 + 4 * self.num_particles as u64 + 4)
+    }
+
+    fn float(&self, bytes: &[u8]) -> f32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.big_endian { f32::from_be_bytes(bytes) } else { f32::from_le_bytes(bytes) }
+    }
+}
+
+/// A file of a trajectory, of which the frames from `skip` on are used.
+struct Segment<R> {
+    reader: R,
+    /// The position of `reader`, to avoid seeking between frames read in
+    /// order.
+    position: u64,
+    header: Header,
+    skip: usize,
+    num_frames: usize,
+}
+
+impl<R: Read + Seek> Segment<R> {
+    fn new(mut reader: R) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(0))?;
+        let header = Header::read(&mut reader)?;
+        let length = reader.seek(SeekFrom::End(0))?;
+        let num_frames = (length.saturating_sub(header.length) / header.frame_length()) as usize;
+        Ok(Segment { reader, position: length, header, skip: 0, num_frames })
+    }
+
+    fn step(&self, frame: usize) -> usize {
+        self.header.start_step + (self.skip + frame) * self.header.step_interval
+    }
+
+    fn read(&mut self, frame: usize) -> io::Result<Vec<Vector3d>> {
+        let header = &self.header;
+        let offset = header.length + (self.skip + frame) as u64 * header.frame_length();
+        if self.position != offset {
+            self.reader.seek(SeekFrom::Start(offset))?;
+        }
+        let mut buffer = vec![0; header.frame_length() as usize];
+        self.reader.read_exact(&mut buffer)?;
+        self.position = offset + buffer.len() as u64;
+
+        let start = if header.unit_cell { 56 } else { 0 };
+        let n = header.num_particles;
+        let axis = |a: usize, i: usize| {
+            let at = start + a * (4 * n + 8) + 4 + 4 * i;
+            header.float(&buffer[at..at + 4])
+        };
+        Ok((0..n).map(|i| (axis(0, i), axis(1, i), axis(2, i))).collect())
+    }
+}
+
+/// The frames of one or more DCD files, such as the files of a run and its
+/// restarts, as one trajectory.
+pub struct Trajectory<R = BufReader<File>> {
+    segments: Vec<Segment<R>>,
+}
+
+impl Trajectory {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Trajectory::chain(&[path])
+    }
+
+    /// Opens the DCD files of a run and its restarts as one trajectory; see
+    /// `Trajectory::from_readers`.
+    pub fn chain<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        let readers = paths.iter()
+                           .map(|path| Ok(BufReader::new(File::open(path)?)))
+                           .collect::<io::Result<Vec<_>>>()?;
+        Trajectory::from_readers(readers)
+    }
+}
+
+impl<R: Read + Seek> Trajectory<R> {
+    /// Reads the headers of DCD files to be read one after another. The
+    /// first frame of a file is left out if it is at the last step of the
+    /// files before it, since a restarted run writes again the frame it
+    /// restarts from.
+    pub fn from_readers(readers: Vec<R>) -> Result<Self> {
+        if readers.is_empty() {
+            return Err(Error::FileCount { expected: 1, found: 0 });
+        }
+        let mut segments: Vec<Segment<R>> = Vec::new();
+        for reader in readers {
+            let mut segment = Segment::new(reader)?;
+            if let Some(first) = segments.first() {
+                if segment.header.num_particles != first.header.num_particles {
+                    return Err(Error::ParticleCount {
+                        expected: first.header.num_particles,
+                        found: segment.header.num_particles,
+                    });
+                }
+            }
+            let last_step = segments.iter()
+                                    .rev()
+                                    .find(|previous| previous.num_frames > 0)
+                                    .map(|previous| previous.step(previous.num_frames - 1));
+            if segment.num_frames > 0 && last_step == Some(segment.step(0)) {
+                segment.skip = 1;
+                segment.num_frames -= 1;
+            }
+            segments.push(segment);
+        }
+        Ok(Trajectory { segments })
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.segments.iter().map(|segment| segment.num_frames).sum()
+    }
+
+    pub fn num_particles(&self) -> usize {
+        self.segments[0].header.num_particles
+    }
+
+    /// Returns the steps between frames in the header of the first file.
+    pub fn step_interval(&self) -> usize {
+        self.segments[0].header.step_interval
+    }
+
+    /// Returns the time between steps in the header of the first file.
+    pub fn time_step(&self) -> f64 {
+        self.segments[0].header.delta as f64
+    }
+
+    /// Returns the segment of frame `index` and the index of the frame in it.
+    fn locate(&self, mut index: usize) -> Option<(usize, usize)> {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if index < segment.num_frames {
+                return Some((i, index));
+            }
+            index -= segment.num_frames;
+        }
+        None
+    }
+
+    /// Returns the step of frame `index`, from the start and interval in
+    /// the header of its file.
+    pub fn step(&self, index: usize) -> Option<usize> {
+        self.locate(index).map(|(segment, frame)| self.segments[segment].step(frame))
+    }
+
+    /// Returns the time of frame `index`, its step times the time step in
+    /// the header of its file.
+    pub fn time(&self, index: usize) -> Option<f64> {
+        self.locate(index).map(|(segment, frame)| {
+            let segment = &self.segments[segment];
+            segment.step(frame) as f64 * segment.header.delta as f64
+        })
+    }
+
+    /// Returns the index of the first frame at `step`.
+    pub fn index_of_step(&self, step: usize) -> Option<usize> {
+        let mut first = 0;
+        for segment in &self.segments {
+            let header = &segment.header;
+            if step >= segment.step(0) && header.step_interval > 0 {
+                let offset = step - segment.step(0);
+                let frame = offset / header.step_interval;
+                if offset.is_multiple_of(header.step_interval) && frame < segment.num_frames {
+                    return Some(first + frame);
+                }
+            }
+            first += segment.num_frames;
+        }
+        None
+    }
+
+    /// Reads frame `index`.
+    pub fn frame(&mut self, index: usize) -> Result<Frame> {
+        let (segment, frame) = match self.locate(index) {
+            Some(location) => location,
+            None => return Err(Error::FrameIndex { index, num_frames: self.num_frames() }),
+        };
+        let segment = &mut self.segments[segment];
+        Ok(Frame {
+            index,
+            step: segment.step(frame),
+            time: segment.step(frame) as f64 * segment.header.delta as f64,
+            positions: segment.read(frame)?,
+        })
+    }
+
+    /// Returns an iterator over every `stride`-th frame in `range`, where a
+    /// stride of 0 is the same as 1.
+    pub fn frames<B: RangeBounds<usize>>(&mut self, range: B, stride: usize) -> Frames<'_, R> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.num_frames(),
+        };
+        let end = end.min(self.num_frames());
+        Frames { trajectory: self, next: start, end, stride: stride.max(1) }
+    }
+
+    /// Returns an iterator over the frames with a row in `ts`, a time series
+    /// of the same run, paired with the row at their step. With
+    /// `nstep_save`, frame `i` is taken to be at step `step(0) + i *
+    /// nstep_save` instead of its step in the header.
+    pub fn join<'a>(&'a mut self, ts: &'a TimeSeries, nstep_save: Option<usize>) -> Joined<'a, R> {
+        let start = self.step(0).unwrap_or(0);
+        let delta = self.time_step();
+        Joined {
+            frames: self.frames(.., 1),
+            rows: ts.steps.iter().map(|time_step| (time_step.step, time_step)).collect(),
+            start,
+            delta,
+            nstep_save,
+            num_frames: 0,
+            unmatched: 0,
+        }
+    }
+}
+
+/// An iterator over frames of a trajectory; see `Trajectory::frames`.
+pub struct Frames<'a, R: 'a> {
+    trajectory: &'a mut Trajectory<R>,
+    next: usize,
+    end: usize,
+    stride: usize,
+}
+
+impl<'a, R: Read + Seek> Iterator for Frames<'a, R> {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.end {
+            return None;
+        }
+        let index = self.next;
+        self.next += self.stride;
+        Some(self.trajectory.frame(index))
+    }
+}
+
+/// An iterator over the frames of a trajectory with the rows of a time
+/// series at their steps; see `Trajectory::join`.
+pub struct Joined<'a, R: 'a> {
+    frames: Frames<'a, R>,
+    rows: HashMap<i32, &'a TimeStep>,
+    start: usize,
+    delta: f64,
+    nstep_save: Option<usize>,
+    /// The number of frames read so far.
+    pub num_frames: usize,
+    /// The number of frames skipped for having no row.
+    pub unmatched: usize,
+}
+
+impl<'a, R: Read + Seek> Iterator for Joined<'a, R> {
+    type Item = Result<(Frame, &'a TimeStep)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for frame in &mut self.frames {
+            let mut frame = match frame {
+                Ok(frame) => frame,
+                Err(err) => return Some(Err(err)),
+            };
+            self.num_frames += 1;
+            if let Some(nstep_save) = self.nstep_save {
+                frame.step = self.start + frame.index * nstep_save;
+                frame.time = frame.step as f64 * self.delta;
+            }
+            match self.rows.get(&(frame.step as i32)) {
+                Some(&time_step) => return Some(Ok((frame, time_step))),
+                None => self.unmatched += 1,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Returns a little-endian DCD file of `num_frames` frames of three
+    /// particles, with particle i of frame f at `(f, i, -f)`.
+    fn dcd(start_step: u32, num_frames: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let record = |bytes: &mut Vec<u8>, data: &[u8]| {
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(data);
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        };
+
+        let mut control = b"CORD".to_vec();
+        let mut integers = [0u32; 20];
+        integers[0] = num_frames;
+        integers[1] = start_step;
+        integers[2] = 100;
+        integers[9] = 0.5f32.to_bits();
+        integers[19] = 24;
+        for integer in &integers {
+            control.extend_from_slice(&integer.to_le_bytes());
+        }
+        record(&mut bytes, &control);
+        let mut title = 2u32.to_le_bytes().to_vec();
+        title.extend_from_slice(&[b' '; 160]);
+        record(&mut bytes, &title);
+        record(&mut bytes, &3u32.to_le_bytes());
+
+        for frame in 0..num_frames {
+            let f = (start_step / 100 + frame) as f32;
+            for axis in 0..3 {
+                let values: Vec<u8> = (0..3).flat_map(|i| {
+                    let value = [f, i as f32, -f][axis];
+                    value.to_le_bytes().to_vec()
+                }).collect();
+                record(&mut bytes, &values);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_random_access() {
+        let mut trajectory = Trajectory::from_readers(vec![Cursor::new(dcd(1000, 5))]).unwrap();
+        assert_eq!(trajectory.num_frames(), 5);
+        assert_eq!(trajectory.num_particles(), 3);
+        assert_eq!(trajectory.step(2), Some(1200));
+        assert_eq!(trajectory.time(2), Some(600.0));
+        assert_eq!(trajectory.index_of_step(1300), Some(3));
+        assert_eq!(trajectory.index_of_step(1350), None);
+        assert_eq!(trajectory.step(5), None);
+
+        let frame = trajectory.frame(3).unwrap();
+        assert_eq!((frame.index, frame.step), (3, 1300));
+        assert_eq!(frame.positions, vec![(13.0, 0.0, -13.0), (13.0, 1.0, -13.0), (13.0, 2.0, -13.0)]);
+        assert_eq!(trajectory.frame(0).unwrap().positions[2], (10.0, 2.0, -10.0));
+        assert!(trajectory.frame(5).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut trajectory = Trajectory::from_readers(vec![Cursor::new(dcd(0, 10))]).unwrap();
+        let steps = |frames: Frames<Cursor<Vec<u8>>>| -> Vec<usize> {
+            frames.map(|frame| frame.unwrap().step).collect()
+        };
+        assert_eq!(steps(trajectory.frames(.., 1)).len(), 10);
+        assert_eq!(steps(trajectory.frames(2..8, 3)), vec![200, 500]);
+        assert_eq!(steps(trajectory.frames(7.., 0)), vec![700, 800, 900]);
+        assert_eq!(steps(trajectory.frames(..=1, 1)), vec![0, 100]);
+        assert!(steps(trajectory.frames(20.., 1)).is_empty());
+    }
+
+    #[test]
+    fn test_chain() {
+        // A run of frames 0..4 restarted from step 300, writing it again.
+        let mut partial = dcd(300, 4);
+        partial.extend_from_slice(&[0; 10]);
+        let readers = vec![Cursor::new(dcd(0, 4)), Cursor::new(partial)];
+        let mut trajectory = Trajectory::from_readers(readers).unwrap();
+        assert_eq!(trajectory.num_frames(), 7);
+        let steps: Vec<_> = (0..7).map(|i| trajectory.step(i).unwrap()).collect();
+        assert_eq!(steps, vec![0, 100, 200, 300, 400, 500, 600]);
+        assert_eq!(trajectory.index_of_step(400), Some(4));
+        assert_eq!(trajectory.frame(4).unwrap().positions[0], (4.0, 0.0, -4.0));
+        let last: Vec<_> = trajectory.frames(2.., 2).map(|frame| frame.unwrap().positions[0].0).collect();
+        assert_eq!(last, vec![2.0, 4.0, 6.0]);
+
+        // A continuation that does not write its first step again.
+        let readers = vec![Cursor::new(dcd(0, 4)), Cursor::new(dcd(400, 2))];
+        let trajectory = Trajectory::from_readers(readers).unwrap();
+        assert_eq!(trajectory.num_frames(), 6);
+        assert_eq!(trajectory.step(4), Some(400));
+
+        assert!(Trajectory::from_readers(vec![Cursor::new(b"not a dcd file".to_vec())]).is_err());
+        assert!(Trajectory::<Cursor<Vec<u8>>>::from_readers(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_join() {
+        let ts = TimeSeries::load("#unit       step    tempk\n\
+                                                  0   300.00\n\
+                                                100   300.00\n\
+                                                300   300.00\n".as_bytes()).unwrap();
+        let mut trajectory = Trajectory::from_readers(vec![Cursor::new(dcd(0, 4))]).unwrap();
+        let mut joined = trajectory.join(&ts, None);
+        let steps: Vec<_> = joined.by_ref().map(|pair| {
+            let (frame, time_step) = pair.unwrap();
+            assert_eq!(frame.step as i32, time_step.step);
+            frame.step
+        }).collect();
+        assert_eq!(steps, vec![0, 100, 300]);
+        assert_eq!((joined.num_frames, joined.unmatched), (4, 1));
+
+        let mut joined = trajectory.join(&ts, Some(50));
+        let frames: Vec<_> = joined.by_ref().map(|pair| pair.unwrap().0).collect();
+        let steps: Vec<_> = frames.iter().map(|frame| (frame.index, frame.step, frame.time)).collect();
+        assert_eq!(steps, vec![(0, 0, 0.0), (2, 100, 50.0)]);
+        assert_eq!(joined.unmatched, 2);
+    }
+}
@@ -0,0 +1,311 @@
+//! Reweighting of energies sampled at one temperature to nearby ones, after
+//! Ferrenberg & Swendsen (1988), and multiple-histogram reweighting (WHAM) of
+//! energies sampled at several temperatures, after Ferrenberg & Swendsen (1989).
+
+use std::f64;
+use histogram::{Axis, BOLTZMANN};
+
+/// The energies sampled by a run at a fixed temperature.
+#[derive(Clone, Debug)]
+pub struct Run {
+    pub temperature: f64,
+    pub energies: Vec<f64>,
+}
+
+impl Run {
+    pub fn new(temperature: f64, energies: Vec<f64>) -> Self {
+        Run { temperature, energies }
+    }
+
+    fn beta(&self) -> f64 {
+        1.0 / (BOLTZMANN * self.temperature)
+    }
+}
+
+/// Returns `ln(sum(exp(x)))` of `terms` without overflow.
+fn log_sum_exp<I: IntoIterator<Item = f64>>(terms: I) -> f64 {
+    let terms: Vec<_> = terms.into_iter().collect();
+    let max = terms.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if !max.is_finite() {
+        return max;
+    }
+    max + terms.iter().map(|x| (x - max).exp()).sum::<f64>().ln()
+}
+
+/// Returns the mean of `values` in each bin of the corresponding `energies`,
+/// or `None` for an empty bin.
+pub fn bin_averages(axis: &Axis, energies: &[f64], values: &[f64]) -> Vec<Option<f64>> {
+    let mut sums = vec![0.0; axis.num_bins];
+    let mut counts = vec![0usize; axis.num_bins];
+    for (&energy, &value) in energies.iter().zip(values) {
+        if let Some(i) = axis.index(energy) {
+            sums[i] += value;
+            counts[i] += 1;
+        }
+    }
+    sums.iter().zip(&counts).map(|(&sum, &count)| {
+        if count > 0 { Some(sum / count as f64) } else { None }
+    }).collect()
+}
+
+/// The weights of the samples of a single run at another temperature.
+#[derive(Clone, Debug)]
+pub struct Reweighting {
+    pub temperature: f64,
+    /// The normalized weight of each sample of the run.
+    pub weights: Vec<f64>,
+}
+
+impl Reweighting {
+    pub fn new(run: &Run, temperature: f64) -> Self {
+        let delta = 1.0 / (BOLTZMANN * temperature) - run.beta();
+        let log_weights: Vec<_> = run.energies.iter().map(|energy| -delta * energy).collect();
+        let log_z = log_sum_exp(log_weights.iter().cloned());
+        let weights = log_weights.iter().map(|w| (w - log_z).exp()).collect();
+        Reweighting { temperature, weights }
+    }
+
+    /// Returns the average at the temperature of a quantity sampled along
+    /// with the energies of the run.
+    pub fn average(&self, values: &[f64]) -> f64 {
+        self.weights.iter().zip(values).map(|(w, value)| w * value).sum()
+    }
+
+    /// Returns the heat capacity `(<E^2> - <E>^2) / kT^2` in kcal/(mol K)
+    /// from the energies of the run.
+    pub fn heat_capacity(&self, energies: &[f64]) -> f64 {
+        let mean = self.average(energies);
+        let variance: f64 = self.weights.iter()
+                                        .zip(energies)
+                                        .map(|(w, e)| w * (e - mean).powi(2))
+                                        .sum();
+        variance / (BOLTZMANN * self.temperature.powi(2))
+    }
+
+    /// Returns the effective sample size `1 / sum(w^2)`, which is the number
+    /// of samples at the original temperature and drops as a few samples
+    /// come to dominate the averages.
+    pub fn effective_sample_size(&self) -> f64 {
+        1.0 / self.weights.iter().map(|w| w * w).sum::<f64>()
+    }
+}
+
+/// The density of states estimated from runs at several temperatures.
+#[derive(Clone, Debug)]
+pub struct DensityOfStates {
+    pub axis: Axis,
+    /// The mean energy of the samples in each bin.
+    pub energies: Vec<f64>,
+    /// The logarithm of the density of states of each bin up to a constant,
+    /// or `None` for a bin without samples.
+    pub log_g: Vec<Option<f64>>,
+    /// The dimensionless free energy `-ln Z` of each run, relative to the first.
+    pub free_energies: Vec<f64>,
+    pub iterations: usize,
+}
+
+impl DensityOfStates {
+    /// Solves the WHAM equations for `runs` with energies binned into
+    /// `num_bins`, iterating until the free energies change less than
+    /// `tolerance`. Returns `None` if there are no samples.
+    pub fn estimate(runs: &[Run], num_bins: usize, tolerance: f64,
+                    max_iterations: usize) -> Option<Self> {
+        let samples: Vec<_> = runs.iter().flat_map(|run| run.energies.iter().cloned()).collect();
+        let axis = Axis::spanning(&samples, num_bins)?;
+        let energies: Vec<_> = bin_averages(&axis, &samples, &samples)
+            .iter()
+            .enumerate()
+            .map(|(i, energy)| energy.unwrap_or_else(|| axis.center(i)))
+            .collect();
+
+        let mut counts = vec![0.0f64; num_bins];
+        for &energy in &samples {
+            if let Some(i) = axis.index(energy) {
+                counts[i] += 1.0;
+            }
+        }
+        let log_counts: Vec<_> = runs.iter().map(|run| (run.energies.len() as f64).ln()).collect();
+        let betas: Vec<_> = runs.iter().map(Run::beta).collect();
+
+        let mut free_energies = vec![0.0; runs.len()];
+        let mut log_g = vec![None; num_bins];
+        let mut iterations = 0;
+        while iterations < max_iterations {
+            iterations += 1;
+            for (i, g) in log_g.iter_mut().enumerate() {
+                if counts[i] == 0.0 {
+                    continue;
+                }
+                let denominator = log_sum_exp((0..runs.len()).map(|k| {
+                    log_counts[k] + free_energies[k] - betas[k] * energies[i]
+                }));
+                *g = Some(counts[i].ln() - denominator);
+            }
+
+            let mut updated: Vec<_> = betas.iter().map(|&beta| {
+                -log_sum_exp(log_g.iter().zip(&energies).filter_map(|(g, &energy)| {
+                    g.map(|g| g - beta * energy)
+                }))
+            }).collect();
+            let offset = updated[0];
+            for f in &mut updated {
+                *f -= offset;
+            }
+
+            let change = updated.iter()
+                                .zip(&free_energies)
+                                .map(|(a, b)| (a - b).abs())
+                                .fold(0.0, f64::max);
+            free_energies = updated;
+            if change < tolerance {
+                break;
+            }
+        }
+
+        Some(DensityOfStates { axis, energies, log_g, free_energies, iterations })
+    }
+
+    /// Returns the canonical probability of each bin at `temperature`.
+    pub fn probabilities(&self, temperature: f64) -> Vec<f64> {
+        let beta = 1.0 / (BOLTZMANN * temperature);
+        let log_weights: Vec<_> = self.log_g.iter().zip(&self.energies).map(|(g, &energy)| {
+            g.map_or(f64::NEG_INFINITY, |g| g - beta * energy)
+        }).collect();
+        let log_z = log_sum_exp(log_weights.iter().cloned());
+        log_weights.iter().map(|w| (w - log_z).exp()).collect()
+    }
+
+    /// Returns the canonical average at `temperature` of a quantity whose
+    /// mean in each bin is given by `values`, such as from `bin_averages`.
+    pub fn average(&self, temperature: f64, values: &[Option<f64>]) -> f64 {
+        let mut sum = 0.0;
+        let mut norm = 0.0;
+        for (p, value) in self.probabilities(temperature).iter().zip(values) {
+            if let Some(value) = *value {
+                sum += p * value;
+                norm += p;
+            }
+        }
+        sum / norm
+    }
+
+    pub fn mean_energy(&self, temperature: f64) -> f64 {
+        self.probabilities(temperature).iter().zip(&self.energies).map(|(p, e)| p * e).sum()
+    }
+
+    /// Returns the heat capacity `(<E^2> - <E>^2) / kT^2` in kcal/(mol K).
+    pub fn heat_capacity(&self, temperature: f64) -> f64 {
+        let probabilities = self.probabilities(temperature);
+        let mean = self.mean_energy(temperature);
+        let variance: f64 = probabilities.iter()
+                                         .zip(&self.energies)
+                                         .map(|(p, e)| p * (e - mean).powi(2))
+                                         .sum();
+        variance / (BOLTZMANN * temperature.powi(2))
+    }
+
+    /// Returns the temperature of the heat capacity peak, searched among
+    /// `num_points` temperatures in `[min, max]` and refined by a parabola
+    /// through its neighbours. Returns `None` if the peak is at either end.
+    pub fn melting_temperature(&self, min: f64, max: f64, num_points: usize) -> Option<f64> {
+        if num_points < 3 {
+            return None;
+        }
+        let step = (max - min) / (num_points - 1) as f64;
+        let cv: Vec<_> = (0..num_points).map(|i| self.heat_capacity(min + i as f64 * step)).collect();
+        let peak = (0..num_points).fold(0, |best, i| if cv[i] > cv[best] { i } else { best });
+        if peak == 0 || peak == num_points - 1 {
+            return None;
+        }
+        let (left, center, right) = (cv[peak - 1], cv[peak], cv[peak + 1]);
+        let curvature = left - 2.0 * center + right;
+        let shift = if curvature < 0.0 { 0.5 * (left - right) / curvature } else { 0.0 };
+        Some(min + (peak as f64 + shift) * step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::Lcg;
+    use statistics;
+
+    /// The energies of `dof` classical harmonic degrees of freedom, whose
+    /// heat capacity is `dof k / 2` at any temperature.
+    fn harmonic(rng: &mut Lcg, dof: usize, temperature: f64, n: usize) -> Vec<f64> {
+        let kt = BOLTZMANN * temperature;
+        (0..n).map(|_| (0..dof).map(|_| 0.5 * kt * rng.normal().powi(2)).sum()).collect()
+    }
+
+    #[test]
+    fn test_log_sum_exp() {
+        assert!((log_sum_exp(vec![1000.0, 1000.0]) - (1000.0 + 2f64.ln())).abs() < 1e-9);
+        assert_eq!(log_sum_exp(vec![f64::NEG_INFINITY]), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_reweighting() {
+        let dof = 20;
+        let mut rng = Lcg(3);
+        let run = Run::new(300.0, harmonic(&mut rng, dof, 300.0, 50000));
+        let n = run.energies.len() as f64;
+
+        let same = Reweighting::new(&run, 300.0);
+        assert!((same.effective_sample_size() - n).abs() < 1e-6 * n);
+        assert!((same.average(&run.energies) - statistics::mean(&run.energies)).abs() < 1e-9);
+
+        let cv = dof as f64 * BOLTZMANN / 2.0;
+        let near = Reweighting::new(&run, 305.0);
+        assert!((near.average(&run.energies) - cv * 305.0).abs() < 0.01 * cv * 305.0);
+        assert!((near.heat_capacity(&run.energies) - cv).abs() < 0.1 * cv);
+
+        let far = Reweighting::new(&run, 400.0);
+        assert!(far.effective_sample_size() < 0.5 * near.effective_sample_size());
+    }
+
+    #[test]
+    fn test_harmonic_oscillators() {
+        let dof = 20;
+        let mut rng = Lcg(7);
+        let runs: Vec<_> = [280.0, 300.0, 320.0].iter()
+                                                .map(|&t| Run::new(t, harmonic(&mut rng, dof, t, 20000)))
+                                                .collect();
+        let dos = DensityOfStates::estimate(&runs, 200, 1e-8, 10000).unwrap();
+        assert!((dos.free_energies[0]).abs() < 1e-12);
+
+        let cv = dof as f64 * BOLTZMANN / 2.0;
+        for &t in &[290.0, 310.0] {
+            let energy = dos.mean_energy(t);
+            assert!((energy - cv * t).abs() < 0.02 * cv * t, "<E>({}) = {}", t, energy);
+            let heat_capacity = dos.heat_capacity(t);
+            assert!((heat_capacity - cv).abs() < 0.1 * cv, "Cv({}) = {}", t, heat_capacity);
+        }
+    }
+
+    #[test]
+    fn test_melting_temperature() {
+        // Two states: folded at energy 0 and unfolded 50 kcal/mol above it
+        // with an entropy gain that puts the midpoint at 330 K.
+        let tm = 330.0;
+        let enthalpy = 50.0;
+        let dos = DensityOfStates {
+            axis: Axis::new(0.0, enthalpy, 2),
+            energies: vec![0.0, enthalpy],
+            log_g: vec![Some(0.0), Some(enthalpy / (BOLTZMANN * tm))],
+            free_energies: vec![0.0],
+            iterations: 0,
+        };
+        assert!((dos.probabilities(tm)[1] - 0.5).abs() < 1e-12);
+        assert!((dos.average(tm, &[Some(1.0), Some(0.0)]) - 0.5).abs() < 1e-12);
+
+        let peak = dos.melting_temperature(300.0, 360.0, 61).unwrap();
+        let fine = (0..60001).map(|i| 300.0 + i as f64 * 0.001)
+                             .fold((0.0, 0.0), |best, t| {
+                                 let cv = dos.heat_capacity(t);
+                                 if cv > best.1 { (t, cv) } else { best }
+                             });
+        assert!((peak - fine.0).abs() < 0.05, "{} vs {}", peak, fine.0);
+        assert!((peak - tm).abs() < 2.0);
+        assert!(dos.melting_temperature(340.0, 360.0, 21).is_none());
+    }
+}